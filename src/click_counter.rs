@@ -0,0 +1,37 @@
+use std::time::{Duration, Instant};
+
+use massive_geometry::Point;
+
+/// Counts consecutive clicks of a mouse button.
+///
+/// A press continues the series if it happens within the interval after the previous one and
+/// nearly at the same position.
+#[derive(Debug)]
+pub struct ClickCounter {
+    interval: Duration,
+    last_press: Option<(Instant, Point, usize)>,
+}
+
+impl ClickCounter {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_press: None,
+        }
+    }
+
+    /// Register a press and return the number of consecutive clicks including this one.
+    pub fn press(&mut self, when: Instant, pos: Point, max_distance: f64) -> usize {
+        let count = match self.last_press {
+            Some((last_when, last_pos, count))
+                if when.duration_since(last_when) <= self.interval
+                    && (pos.x - last_pos.x).hypot(pos.y - last_pos.y) <= max_distance =>
+            {
+                count + 1
+            }
+            _ => 1,
+        };
+        self.last_press = Some((when, pos, count));
+        count
+    }
+}
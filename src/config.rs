@@ -0,0 +1,237 @@
+//! The user configuration of the terminal.
+use std::sync::LazyLock;
+
+use fancy_regex::Regex;
+use serde::Deserialize;
+use termwiz::hyperlink::{self, Rule};
+use wezterm_bidi::ParagraphDirectionHint;
+use winit::keyboard::ModifiersState;
+
+use crate::key_bindings::KeyBindings;
+
+/// The configuration of a terminal instance.
+///
+/// It's passed as part of the instance's parameters. All fields are optional.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Render lines in their visual order as defined by the Unicode bidirectional algorithm.
+    pub bidi_enabled: bool,
+    /// The paragraph direction used when `bidi_enabled` is set.
+    pub bidi_direction: BidiDirection,
+
+    /// Render bold text that uses the palette colors 0-7 with their bright variants 8-15.
+    pub bold_brightens_ansi_colors: bool,
+    /// The minimum contrast ratio between the foreground and background colors of a cell, ranging
+    /// from 1 (no adjustment) to 21 (black and white only).
+    ///
+    /// Foreground colors that don't reach this ratio get lighter or darker.
+    pub minimum_contrast: f32,
+
+    /// How words are found when double-clicking.
+    pub word_selection: WordSelection,
+    /// The characters that separate words when `word_selection` is `Boundary`.
+    pub word_boundary: String,
+    /// Regular expressions that select their match on double-click if it covers the clicked cell.
+    ///
+    /// They take precedence over word selection, the first rule that matches wins.
+    pub smart_selection_rules: SmartSelectionRules,
+    /// The maximum time between the presses of a double, triple or quadruple click.
+    pub multi_click_interval_ms: u64,
+    /// Copy the selection when it's completed with the mouse.
+    ///
+    /// On Linux, the selection is copied to the PRIMARY selection, which is pasted with a middle
+    /// click. Other platforms use the clipboard.
+    pub copy_on_select: bool,
+    /// The number of Up or Down keys a wheel step sends on the alternate screen if the
+    /// application does not report the mouse, so that pagers like `less` scroll. Trackpads send
+    /// one key per line they scroll.
+    ///
+    /// `0` disables sending keys.
+    pub alternate_screen_wheel_lines: usize,
+    /// The modifier that, while held, selects text and opens hyperlinks with the mouse even if the
+    /// application reports the mouse.
+    pub mouse_reporting_bypass_modifier: BypassModifier,
+
+    /// Key chords mapped to actions, added to the platform defaults.
+    ///
+    /// Chords mapped to `null` are unbound and sent to the application.
+    pub key_bindings: KeyBindings,
+    /// Allow applications to enable the progressive enhancements of the kitty keyboard protocol,
+    /// like unambiguous escape codes, key release events, and reporting modifier keys.
+    pub enable_kitty_keyboard: bool,
+    /// Encode keys that are ambiguous in the legacy encoding, like Ctrl+I and Tab, as CSI u
+    /// sequences, even if the application did not ask for it.
+    pub enable_csi_u_key_encoding: bool,
+    /// The Alt keys that are used as Meta and send an ESC prefix.
+    ///
+    /// The other Alt keys compose characters, like Option on macOS or AltGr.
+    pub alt_as_meta: AltAsMeta,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bidi_enabled: false,
+            bidi_direction: BidiDirection::default(),
+            bold_brightens_ansi_colors: true,
+            minimum_contrast: 1.0,
+            word_selection: WordSelection::default(),
+            word_boundary: DEFAULT_WORD_BOUNDARY.into(),
+            smart_selection_rules: SmartSelectionRules::default(),
+            multi_click_interval_ms: 500,
+            copy_on_select: cfg!(target_os = "linux"),
+            alternate_screen_wheel_lines: 3,
+            mouse_reporting_bypass_modifier: BypassModifier::default(),
+            key_bindings: KeyBindings::default(),
+            enable_kitty_keyboard: true,
+            enable_csi_u_key_encoding: false,
+            alt_as_meta: AltAsMeta::default(),
+        }
+    }
+}
+
+impl Config {
+    /// The direction hint to use for resolving the visual order of lines, `None` if bidi is
+    /// disabled.
+    pub fn bidi_hint(&self) -> Option<ParagraphDirectionHint> {
+        self.bidi_enabled.then(|| self.bidi_direction.into())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum BidiDirection {
+    #[default]
+    AutoLeftToRight,
+    AutoRightToLeft,
+    LeftToRight,
+    RightToLeft,
+}
+
+impl From<BidiDirection> for ParagraphDirectionHint {
+    fn from(direction: BidiDirection) -> Self {
+        match direction {
+            BidiDirection::AutoLeftToRight => ParagraphDirectionHint::AutoLeftToRight,
+            BidiDirection::AutoRightToLeft => ParagraphDirectionHint::AutoRightToLeft,
+            BidiDirection::LeftToRight => ParagraphDirectionHint::LeftToRight,
+            BidiDirection::RightToLeft => ParagraphDirectionHint::RightToLeft,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum WordSelection {
+    /// Words are separated by the characters of `word_boundary`.
+    #[default]
+    Boundary,
+    /// Words are segmented as defined by Unicode Standard Annex #29.
+    Unicode,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum AltAsMeta {
+    #[default]
+    Both,
+    Left,
+    Right,
+    Neither,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum BypassModifier {
+    #[default]
+    Shift,
+    Ctrl,
+    Alt,
+    Super,
+}
+
+impl BypassModifier {
+    pub fn is_pressed(self, state: ModifiersState) -> bool {
+        match self {
+            Self::Shift => state.shift_key(),
+            Self::Ctrl => state.control_key(),
+            Self::Alt => state.alt_key(),
+            Self::Super => state.super_key(),
+        }
+    }
+}
+
+const DEFAULT_WORD_BOUNDARY: &str = " \t\n{[}]()\"'`";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "Vec<String>")]
+pub struct SmartSelectionRules(Vec<Regex>);
+
+impl SmartSelectionRules {
+    pub fn iter(&self) -> impl Iterator<Item = &Regex> {
+        self.0.iter()
+    }
+}
+
+impl TryFrom<Vec<String>> for SmartSelectionRules {
+    type Error = fancy_regex::Error;
+
+    fn try_from(patterns: Vec<String>) -> Result<Self, Self::Error> {
+        patterns
+            .iter()
+            .map(|pattern| Regex::new(pattern))
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+impl Default for SmartSelectionRules {
+    fn default() -> Self {
+        Self::try_from(
+            DEFAULT_SMART_SELECTION_RULES
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect::<Vec<_>>(),
+        )
+        .expect("Internal error: Invalid default smart selection rule")
+    }
+}
+
+const DEFAULT_SMART_SELECTION_RULES: &[&str] = &[
+    // URLs
+    r#"\b[a-zA-Z][a-zA-Z0-9+.-]*://[^\s<>"'`]*[^\s<>"'`.,;:!?)\]]"#,
+    // UUIDs
+    r"\b[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}\b",
+    // IPv4 addresses with an optional port
+    r"\b(?:\d{1,3}\.){3}\d{1,3}(?::\d+)?\b",
+    // IPv6 addresses, full and compressed
+    r"\b(?:[0-9a-fA-F]{1,4}:){7}[0-9a-fA-F]{1,4}\b",
+    r"\b(?:[0-9a-fA-F]{1,4}:){1,6}:(?:[0-9a-fA-F]{1,4}:){0,5}[0-9a-fA-F]{1,4}\b",
+    // File paths with an optional `:line:column`
+    r"(?:[\w.@+~-]*/)+[\w.@+-]+(?::\d+){0,2}",
+    // File names with `:line:column`
+    r"[\w.@+-]+\.\w+(?::\d+){1,2}",
+    // Git SHAs, abbreviated or full
+    //
+    // Detail: The lookaheads require a digit and a letter, so that numbers and words like
+    // `decade` are not taken for SHAs.
+    r"\b(?=[0-9a-f]*[0-9])(?=[0-9a-f]*[a-f])[0-9a-f]{7,40}\b",
+    // Quoted strings on a single line
+    //
+    // Detail: `\B` next to the quotes rejects word characters right outside of them. Single
+    // quotes are not included, because they are mostly used as apostrophes.
+    r#"\B"[^"\n]*"\B|\B`[^`\n]*`\B"#,
+];
+
+pub static DEFAULT_HYPERLINK_RULES: LazyLock<Vec<Rule>> = LazyLock::new(|| {
+    vec![
+        // First handle URLs wrapped with punctuation (i.e. brackets)
+        // e.g. [http://foo] (http://foo) <http://foo>
+        Rule::with_highlight(r"\((\w+://\S+)\)", "$1", 1).unwrap(),
+        Rule::with_highlight(r"\[(\w+://\S+)\]", "$1", 1).unwrap(),
+        Rule::with_highlight(r"<(\w+://\S+)>", "$1", 1).unwrap(),
+        // Then handle URLs not wrapped in brackets that
+        // 1) have a balanced ending parenthesis or
+        Rule::new(hyperlink::CLOSING_PARENTHESIS_HYPERLINK_PATTERN, "$0").unwrap(),
+        // 2) include terminating _, / or - characters, if any
+        Rule::new(hyperlink::GENERIC_HYPERLINK_PATTERN, "$0").unwrap(),
+        // implicit mailto link
+        Rule::new(r"\b\w+@[\w-]+(\.[\w-]+)+\b", "mailto:$0").unwrap(),
+    ]
+});
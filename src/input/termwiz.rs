@@ -4,8 +4,10 @@
 //! (as used by `wezterm-term`).
 
 use winit::{
-    event::{self, DeviceId, ElementState, KeyEvent, MouseScrollDelta, TouchPhase},
-    keyboard::{Key, NamedKey},
+    event::{
+        self, DeviceId, ElementState, KeyEvent, ModifiersKeyState, MouseScrollDelta, TouchPhase,
+    },
+    keyboard::{Key, KeyLocation, NamedKey, PhysicalKey},
 };
 
 use termwiz::input::{KeyCode, Modifiers};
@@ -15,21 +17,231 @@ use massive_applications::ViewEvent;
 use massive_geometry::Point;
 use massive_input::Event;
 
+use crate::config::AltAsMeta;
+
 /// Convert a full `winit` `KeyEvent` to a `(KeyCode, Modifiers)` pair.
 /// Returns `None` when the event shouldn't be forwarded to the terminal
-/// (e.g. pure modifier changes or unsupported keys).
+/// (e.g. unsupported keys).
+///
+/// Alt keys that are not used as Meta are removed from the modifiers, so that the characters they
+/// compose are sent without an ESC prefix.
+///
+/// `keypad_codes` is set if the terminal encodes the keypad's digits and operators apart from the
+/// characters they produce.
 pub fn convert_key_event(
     event: &KeyEvent,
     mods: winit::event::Modifiers,
+    alt_keys: &AltKeys,
+    alt_as_meta: AltAsMeta,
+    keypad_codes: bool,
 ) -> Option<(KeyCode, Modifiers)> {
-    let keycode = convert_key(&event.logical_key)?;
-    let converted_mods = convert_modifiers(mods);
+    let keycode = convert_modifier_key(&event.logical_key, event.location)
+        .or_else(|| convert_numpad_key(event, keypad_codes))
+        .or_else(|| convert_key(&event.logical_key))?;
+    let converted_mods = apply_alt_as_meta(alt_keys.apply(convert_modifiers(mods)), alt_as_meta);
     Some((keycode, converted_mods))
 }
 
+/// The Alt keys that are held down, tracked from their key events.
+///
+/// Detail: X11 and Wayland don't report the state of the left and right modifier keys.
+#[derive(Debug, Default)]
+pub struct AltKeys {
+    left: bool,
+    right: bool,
+}
+
+impl AltKeys {
+    pub fn track(&mut self, event: &KeyEvent) {
+        if event.logical_key != Key::Named(NamedKey::Alt) {
+            return;
+        }
+        let pressed = event.state == ElementState::Pressed;
+        match event.location {
+            KeyLocation::Left => self.left = pressed,
+            KeyLocation::Right => self.right = pressed,
+            _ => {}
+        }
+    }
+
+    /// Forget the pressed keys, for example when the focus is lost and their releases are not
+    /// received.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Add the tracked Alt keys to the modifiers if the platform did not report them.
+    fn apply(&self, mods: Modifiers) -> Modifiers {
+        if !mods.contains(Modifiers::ALT)
+            || mods.intersects(Modifiers::LEFT_ALT | Modifiers::RIGHT_ALT)
+        {
+            return mods;
+        }
+        let mut mods = mods;
+        if self.left {
+            mods |= Modifiers::LEFT_ALT;
+        }
+        if self.right {
+            mods |= Modifiers::RIGHT_ALT;
+        }
+        mods
+    }
+}
+
+/// Remove the Alt modifier if none of the pressed Alt keys is used as Meta.
+///
+/// Detail: If the platform does not report which Alt key is pressed, Alt is always used as Meta.
+fn apply_alt_as_meta(mods: Modifiers, alt_as_meta: AltAsMeta) -> Modifiers {
+    if !mods.contains(Modifiers::ALT) {
+        return mods;
+    }
+    let (left, right) = (
+        mods.contains(Modifiers::LEFT_ALT),
+        mods.contains(Modifiers::RIGHT_ALT),
+    );
+    let meta = match alt_as_meta {
+        _ if !left && !right => true,
+        AltAsMeta::Both => true,
+        AltAsMeta::Left => left,
+        AltAsMeta::Right => right,
+        AltAsMeta::Neither => false,
+    };
+    if meta {
+        mods
+    } else {
+        mods - (Modifiers::ALT | Modifiers::LEFT_ALT | Modifiers::RIGHT_ALT)
+    }
+}
+
+/// The text of a key event if it differs from what the key is converted to.
+///
+/// This is the case for characters composed with dead keys or compose sequences, and for keys
+/// that produce more than one character. Returns `None` if modifiers other than shift are
+/// pressed, so that shortcuts and control characters are processed as keys.
+///
+/// `mods` are the converted modifiers of the event.
+pub fn composed_text(event: &KeyEvent, mods: Modifiers) -> Option<&str> {
+    let text = event.text.as_deref()?;
+    if mods.intersects(Modifiers::CTRL | Modifiers::ALT | Modifiers::SUPER) {
+        return None;
+    }
+    if text.chars().any(char::is_control) {
+        return None;
+    }
+    match &event.logical_key {
+        Key::Character(key) if key.as_str() == text && text.chars().count() == 1 => None,
+        Key::Named(NamedKey::Space) => None,
+        _ => Some(text),
+    }
+}
+
+/// Convert a `winit` `PhysicalKey` to the `KeyCode` the key produces on a US QWERTY layout.
+///
+/// Returns `None` for keys that can't be bound physically.
+pub fn convert_physical_key(physical: &PhysicalKey) -> Option<KeyCode> {
+    use winit::keyboard::KeyCode as K;
+
+    let PhysicalKey::Code(code) = physical else {
+        return None;
+    };
+
+    Some(match code {
+        K::KeyA => KeyCode::Char('a'),
+        K::KeyB => KeyCode::Char('b'),
+        K::KeyC => KeyCode::Char('c'),
+        K::KeyD => KeyCode::Char('d'),
+        K::KeyE => KeyCode::Char('e'),
+        K::KeyF => KeyCode::Char('f'),
+        K::KeyG => KeyCode::Char('g'),
+        K::KeyH => KeyCode::Char('h'),
+        K::KeyI => KeyCode::Char('i'),
+        K::KeyJ => KeyCode::Char('j'),
+        K::KeyK => KeyCode::Char('k'),
+        K::KeyL => KeyCode::Char('l'),
+        K::KeyM => KeyCode::Char('m'),
+        K::KeyN => KeyCode::Char('n'),
+        K::KeyO => KeyCode::Char('o'),
+        K::KeyP => KeyCode::Char('p'),
+        K::KeyQ => KeyCode::Char('q'),
+        K::KeyR => KeyCode::Char('r'),
+        K::KeyS => KeyCode::Char('s'),
+        K::KeyT => KeyCode::Char('t'),
+        K::KeyU => KeyCode::Char('u'),
+        K::KeyV => KeyCode::Char('v'),
+        K::KeyW => KeyCode::Char('w'),
+        K::KeyX => KeyCode::Char('x'),
+        K::KeyY => KeyCode::Char('y'),
+        K::KeyZ => KeyCode::Char('z'),
+        K::Digit0 => KeyCode::Char('0'),
+        K::Digit1 => KeyCode::Char('1'),
+        K::Digit2 => KeyCode::Char('2'),
+        K::Digit3 => KeyCode::Char('3'),
+        K::Digit4 => KeyCode::Char('4'),
+        K::Digit5 => KeyCode::Char('5'),
+        K::Digit6 => KeyCode::Char('6'),
+        K::Digit7 => KeyCode::Char('7'),
+        K::Digit8 => KeyCode::Char('8'),
+        K::Digit9 => KeyCode::Char('9'),
+        K::Backquote => KeyCode::Char('`'),
+        K::Minus => KeyCode::Char('-'),
+        K::Equal => KeyCode::Char('='),
+        K::BracketLeft => KeyCode::Char('['),
+        K::BracketRight => KeyCode::Char(']'),
+        K::Backslash => KeyCode::Char('\\'),
+        K::Semicolon => KeyCode::Char(';'),
+        K::Quote => KeyCode::Char('\''),
+        K::Comma => KeyCode::Char(','),
+        K::Period => KeyCode::Char('.'),
+        K::Slash => KeyCode::Char('/'),
+        K::Space => KeyCode::Char(' '),
+        K::Enter => KeyCode::Enter,
+        K::Tab => KeyCode::Tab,
+        K::Backspace => KeyCode::Backspace,
+        K::Escape => KeyCode::Escape,
+        K::Delete => KeyCode::Delete,
+        K::Insert => KeyCode::Insert,
+        K::Home => KeyCode::Home,
+        K::End => KeyCode::End,
+        K::PageUp => KeyCode::PageUp,
+        K::PageDown => KeyCode::PageDown,
+        K::ArrowUp => KeyCode::UpArrow,
+        K::ArrowDown => KeyCode::DownArrow,
+        K::ArrowLeft => KeyCode::LeftArrow,
+        K::ArrowRight => KeyCode::RightArrow,
+        K::F1 => KeyCode::Function(1),
+        K::F2 => KeyCode::Function(2),
+        K::F3 => KeyCode::Function(3),
+        K::F4 => KeyCode::Function(4),
+        K::F5 => KeyCode::Function(5),
+        K::F6 => KeyCode::Function(6),
+        K::F7 => KeyCode::Function(7),
+        K::F8 => KeyCode::Function(8),
+        K::F9 => KeyCode::Function(9),
+        K::F10 => KeyCode::Function(10),
+        K::F11 => KeyCode::Function(11),
+        K::F12 => KeyCode::Function(12),
+        K::F13 => KeyCode::Function(13),
+        K::F14 => KeyCode::Function(14),
+        K::F15 => KeyCode::Function(15),
+        K::F16 => KeyCode::Function(16),
+        K::F17 => KeyCode::Function(17),
+        K::F18 => KeyCode::Function(18),
+        K::F19 => KeyCode::Function(19),
+        K::F20 => KeyCode::Function(20),
+        K::F21 => KeyCode::Function(21),
+        K::F22 => KeyCode::Function(22),
+        K::F23 => KeyCode::Function(23),
+        K::F24 => KeyCode::Function(24),
+        _ => return None,
+    })
+}
+
 /// Convert a `winit` `ModifiersState` to `termwiz` `Modifiers`.
+///
+/// Includes the positional `LEFT_*` and `RIGHT_*` modifiers if the platform reports them.
 pub fn convert_modifiers(mods: winit::event::Modifiers) -> Modifiers {
-    // Robustness: Can we provide a more detailed state, LEFT / RIGHT CONTROL / LEFT / RIGHT SHIFT?
+    // Precision: winit does not report the Hyper and Meta modifiers, so they are never encoded.
+    let pressed = |state| state == ModifiersKeyState::Pressed;
     let state = mods.state();
     let mut out = Modifiers::NONE;
     if state.shift_key() {
@@ -44,6 +256,18 @@ pub fn convert_modifiers(mods: winit::event::Modifiers) -> Modifiers {
     if state.super_key() {
         out |= Modifiers::SUPER;
     }
+    for (state, positional) in [
+        (mods.lshift_state(), Modifiers::LEFT_SHIFT),
+        (mods.rshift_state(), Modifiers::RIGHT_SHIFT),
+        (mods.lcontrol_state(), Modifiers::LEFT_CTRL),
+        (mods.rcontrol_state(), Modifiers::RIGHT_CTRL),
+        (mods.lalt_state(), Modifiers::LEFT_ALT),
+        (mods.ralt_state(), Modifiers::RIGHT_ALT),
+    ] {
+        if pressed(state) {
+            out |= positional;
+        }
+    }
     out
 }
 
@@ -52,16 +276,118 @@ pub fn convert_modifiers(mods: winit::event::Modifiers) -> Modifiers {
 fn convert_key(key: &Key) -> Option<KeyCode> {
     match key {
         Key::Character(s) => {
-            // Return None for empty strings; take first char if multi-char (IME preedit may send more).
+            // Return None for empty strings; take first char if multi-char. Keys that produce more
+            // than one character are sent with `composed_text()`.
             let ch = s.chars().next()?;
             Some(KeyCode::Char(ch))
         }
         Key::Named(named) => convert_named_key(named),
-        // Dead keys and other variants may appear; skip them for now.
+        // Detail: Dead keys are composed by winit, the composed character arrives as the text of
+        // the next key event, see `composed_text()`.
         _ => None,
     }
 }
 
+/// Convert modifier keys, which are reported to applications that enabled the kitty keyboard
+/// protocol's "report all keys as escape codes" enhancement.
+fn convert_modifier_key(key: &Key, location: KeyLocation) -> Option<KeyCode> {
+    let Key::Named(named) = key else {
+        return None;
+    };
+    let (left, right) = match location {
+        KeyLocation::Left => (true, false),
+        KeyLocation::Right => (false, true),
+        _ => (false, false),
+    };
+    Some(match named {
+        NamedKey::Shift if left => KeyCode::LeftShift,
+        NamedKey::Shift if right => KeyCode::RightShift,
+        NamedKey::Shift => KeyCode::Shift,
+        NamedKey::Control if left => KeyCode::LeftControl,
+        NamedKey::Control if right => KeyCode::RightControl,
+        NamedKey::Control => KeyCode::Control,
+        NamedKey::Alt if left => KeyCode::LeftAlt,
+        NamedKey::Alt if right => KeyCode::RightAlt,
+        NamedKey::Alt => KeyCode::Alt,
+        NamedKey::Super if left => KeyCode::LeftWindows,
+        NamedKey::Super if right => KeyCode::RightWindows,
+        NamedKey::Super => KeyCode::Super,
+        NamedKey::Hyper => KeyCode::Hyper,
+        NamedKey::Meta => KeyCode::Meta,
+        _ => return None,
+    })
+}
+
+/// Convert keys on the numeric keypad.
+///
+/// With Num Lock on, the digits and operators are converted to their keypad codes if
+/// `keypad_codes` is set. Otherwise, they produce their characters.
+fn convert_numpad_key(event: &KeyEvent, keypad_codes: bool) -> Option<KeyCode> {
+    use winit::keyboard::KeyCode as K;
+
+    if event.location != KeyLocation::Numpad {
+        return None;
+    }
+
+    Some(match &event.logical_key {
+        // Num Lock off: The keys move the cursor.
+        Key::Named(named) => match named {
+            NamedKey::Home => KeyCode::KeyPadHome,
+            NamedKey::End => KeyCode::KeyPadEnd,
+            NamedKey::PageUp => KeyCode::KeyPadPageUp,
+            NamedKey::PageDown => KeyCode::KeyPadPageDown,
+            NamedKey::Clear => KeyCode::KeyPadBegin,
+            // Detail: Arrows, Insert, Delete, and Enter are the same as on the main keyboard.
+            _ => return None,
+        },
+        _ if !keypad_codes => return None,
+        _ => match event.physical_key {
+            PhysicalKey::Code(K::Numpad0) => KeyCode::Numpad0,
+            PhysicalKey::Code(K::Numpad1) => KeyCode::Numpad1,
+            PhysicalKey::Code(K::Numpad2) => KeyCode::Numpad2,
+            PhysicalKey::Code(K::Numpad3) => KeyCode::Numpad3,
+            PhysicalKey::Code(K::Numpad4) => KeyCode::Numpad4,
+            PhysicalKey::Code(K::Numpad5) => KeyCode::Numpad5,
+            PhysicalKey::Code(K::Numpad6) => KeyCode::Numpad6,
+            PhysicalKey::Code(K::Numpad7) => KeyCode::Numpad7,
+            PhysicalKey::Code(K::Numpad8) => KeyCode::Numpad8,
+            PhysicalKey::Code(K::Numpad9) => KeyCode::Numpad9,
+            PhysicalKey::Code(K::NumpadAdd) => KeyCode::Add,
+            PhysicalKey::Code(K::NumpadSubtract) => KeyCode::Subtract,
+            PhysicalKey::Code(K::NumpadMultiply) => KeyCode::Multiply,
+            PhysicalKey::Code(K::NumpadDivide) => KeyCode::Divide,
+            PhysicalKey::Code(K::NumpadDecimal) => KeyCode::Decimal,
+            PhysicalKey::Code(K::NumpadComma) => KeyCode::Separator,
+            _ => return None,
+        },
+    })
+}
+
+/// The character a keypad digit or operator produces with Num Lock on.
+///
+/// Precision: The decimal and the separator key produce `.` and `,` on all keyboard layouts.
+pub fn keypad_char(key: KeyCode) -> Option<char> {
+    Some(match key {
+        KeyCode::Numpad0 => '0',
+        KeyCode::Numpad1 => '1',
+        KeyCode::Numpad2 => '2',
+        KeyCode::Numpad3 => '3',
+        KeyCode::Numpad4 => '4',
+        KeyCode::Numpad5 => '5',
+        KeyCode::Numpad6 => '6',
+        KeyCode::Numpad7 => '7',
+        KeyCode::Numpad8 => '8',
+        KeyCode::Numpad9 => '9',
+        KeyCode::Add => '+',
+        KeyCode::Subtract => '-',
+        KeyCode::Multiply => '*',
+        KeyCode::Divide => '/',
+        KeyCode::Decimal => '.',
+        KeyCode::Separator => ',',
+        _ => return None,
+    })
+}
+
 fn convert_named_key(named: &NamedKey) -> Option<KeyCode> {
     match named {
         NamedKey::CapsLock => Some(KeyCode::CapsLock),
@@ -138,12 +464,18 @@ fn convert_named_key(named: &NamedKey) -> Option<KeyCode> {
 }
 
 /// Convert an `Event<ViewEvent>` to mouse event data for terminal forwarding.
-/// Returns `(MouseEventKind, MouseButton, Point)` if the event is a mouse event.
+/// Returns `(MouseEventKind, MouseButton, Point)` for every mouse event to send.
+///
+/// Wheel events may result in none, or a horizontal and a vertical one.
 pub fn convert_mouse_event_from_view(
     ev: &Event<ViewEvent>,
-) -> Option<(MouseEventKind, MouseButton, Point)> {
+    wheel_steps: &mut WheelSteps,
+    cell_size_px: (f64, f64),
+) -> Vec<(MouseEventKind, MouseButton, Point)> {
     let view_event = ev.event();
-    let pos = ev.pos()?;
+    let Some(pos) = ev.pos() else {
+        return Vec::new();
+    };
 
     let (kind, button) = match view_event {
         ViewEvent::CursorMoved { .. } => (
@@ -151,40 +483,79 @@ pub fn convert_mouse_event_from_view(
             mouse_button_pressed(ev).unwrap_or(MouseButton::None),
         ),
         ViewEvent::MouseWheel {
-            delta: MouseScrollDelta::LineDelta(xd, 0.0),
-            phase: TouchPhase::Moved,
-            ..
-        } => (
-            MouseEventKind::Press,
-            if *xd < 0.0 {
-                MouseButton::WheelLeft((-xd).round() as usize)
-            } else {
-                MouseButton::WheelRight(xd.round() as usize)
-            },
-        ),
-        ViewEvent::MouseWheel {
-            delta: MouseScrollDelta::LineDelta(0.0, yd),
+            delta,
             phase: TouchPhase::Moved,
             ..
-        } => (
-            MouseEventKind::Press,
-            if *yd < 0.0 {
-                MouseButton::WheelUp((-yd).round() as usize)
-            } else {
-                MouseButton::WheelDown(yd.round() as usize)
-            },
-        ),
-        ViewEvent::MouseInput { state, button, .. } => (
-            match state {
-                ElementState::Pressed => MouseEventKind::Press,
-                ElementState::Released => MouseEventKind::Release,
-            },
-            convert_mouse_button(*button)?,
-        ),
-        _ => return None,
+        } => {
+            return wheel_steps
+                .add(*delta, cell_size_px)
+                .into_iter()
+                .map(|button| (MouseEventKind::Press, button, pos))
+                .collect();
+        }
+        ViewEvent::MouseInput { state, button, .. } => {
+            let Some(button) = convert_mouse_button(*button) else {
+                return Vec::new();
+            };
+            (
+                match state {
+                    ElementState::Pressed => MouseEventKind::Press,
+                    ElementState::Released => MouseEventKind::Release,
+                },
+                button,
+            )
+        }
+        _ => return Vec::new(),
     };
 
-    Some((kind, button, pos))
+    vec![(kind, button, pos)]
+}
+
+/// Accumulates scroll deltas into wheel steps.
+///
+/// Pixel deltas of trackpads are converted to lines, the fractions left over are carried over to
+/// the next delta.
+#[derive(Debug, Default)]
+pub struct WheelSteps {
+    /// The horizontal and vertical fractions of a step not sent yet.
+    carry: (f64, f64),
+}
+
+impl WheelSteps {
+    /// Add a delta and return the wheel buttons, horizontal first.
+    ///
+    /// `cell_size_px` converts pixel deltas to steps.
+    pub fn add(&mut self, delta: MouseScrollDelta, cell_size_px: (f64, f64)) -> Vec<MouseButton> {
+        let steps = self.add_steps(delta, cell_size_px);
+
+        let mut buttons = Vec::new();
+        match steps.0 {
+            x if x < 0.0 => buttons.push(MouseButton::WheelLeft(-x as usize)),
+            x if x > 0.0 => buttons.push(MouseButton::WheelRight(x as usize)),
+            _ => {}
+        }
+        match steps.1 {
+            y if y < 0.0 => buttons.push(MouseButton::WheelUp(-y as usize)),
+            y if y > 0.0 => buttons.push(MouseButton::WheelDown(y as usize)),
+            _ => {}
+        }
+        buttons
+    }
+
+    /// Add a delta and return the whole horizontal and vertical steps, signed like the delta.
+    pub fn add_steps(&mut self, delta: MouseScrollDelta, cell_size_px: (f64, f64)) -> (f64, f64) {
+        let (x, y) = match delta {
+            MouseScrollDelta::LineDelta(x, y) => (x as f64, y as f64),
+            MouseScrollDelta::PixelDelta(position) => {
+                (position.x / cell_size_px.0, position.y / cell_size_px.1)
+            }
+        };
+
+        let (x, y) = (self.carry.0 + x, self.carry.1 + y);
+        let steps = (x.trunc(), y.trunc());
+        self.carry = (x - steps.0, y - steps.1);
+        steps
+    }
 }
 
 fn mouse_button_pressed(ev: &Event<ViewEvent>) -> Option<MouseButton> {
@@ -205,6 +576,8 @@ fn convert_mouse_button(button: event::MouseButton) -> Option<MouseButton> {
         event::MouseButton::Left => Some(MouseButton::Left),
         event::MouseButton::Right => Some(MouseButton::Right),
         event::MouseButton::Middle => Some(MouseButton::Middle),
+        // Feature: xterm reports Back and Forward as buttons 8 and 9, but `wezterm_term`'s
+        // `MouseButton` can't represent them.
         event::MouseButton::Back => None,
         event::MouseButton::Forward => None,
         event::MouseButton::Other(_) => None,
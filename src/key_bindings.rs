@@ -0,0 +1,252 @@
+//! Key bindings map key chords to terminal actions.
+//!
+//! Chords are written as modifiers and a key separated by `+`, for example `Ctrl+Shift+C`,
+//! `Super+F`, or `Shift+PageUp`. Keys that are bound are not sent to the application.
+//!
+//! Like in wezterm, a key can be prefixed with `phys:` to bind the physical key at the position of
+//! the key on a US QWERTY layout, or with `mapped:` to bind the character the keyboard layout
+//! produces, which is the default.
+use std::collections::HashMap;
+
+use anyhow::{Result, anyhow, bail};
+use serde::Deserialize;
+use termwiz::input::{KeyCode, Modifiers};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum Action {
+    Copy,
+    /// Copy the selection as HTML and plain text.
+    CopyHtml,
+    Paste,
+    Search,
+    CopyMode,
+    ScrollLineUp,
+    ScrollLineDown,
+    ScrollPageUp,
+    ScrollPageDown,
+    ScrollToTop,
+    ScrollToBottom,
+    /// Scroll the previous shell prompt marked with OSC 133 to the top.
+    ScrollToPreviousPrompt,
+    /// Scroll the next shell prompt marked with OSC 133 to the top.
+    ScrollToNextPrompt,
+    FontBigger,
+    FontSmaller,
+    FontReset,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChordKey {
+    /// The key as produced by the keyboard layout.
+    Mapped(KeyCode),
+    /// The key at a position, named by what it produces on a US QWERTY layout.
+    Physical(KeyCode),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub key: ChordKey,
+    pub modifiers: Modifiers,
+}
+
+impl KeyChord {
+    pub fn new(key: ChordKey, modifiers: Modifiers) -> Self {
+        // Detail: Shift may or may not produce uppercase characters, so characters are compared
+        // in lowercase.
+        let lowercase = |key| match key {
+            KeyCode::Char(c) => KeyCode::Char(c.to_ascii_lowercase()),
+            key => key,
+        };
+        let key = match key {
+            ChordKey::Mapped(key) => ChordKey::Mapped(lowercase(key)),
+            ChordKey::Physical(key) => ChordKey::Physical(lowercase(key)),
+        };
+        Self {
+            key,
+            modifiers: modifiers.remove_positional_mods(),
+        }
+    }
+
+    pub fn parse(chord: &str) -> Result<Self> {
+        let mut parts: Vec<&str> = chord.split('+').map(str::trim).collect();
+        // `Ctrl++` binds the plus key.
+        if chord.ends_with("++") {
+            parts.truncate(parts.len() - 2);
+            parts.push("+");
+        }
+        let Some((key, modifiers)) = parts.split_last() else {
+            bail!("Empty key chord");
+        };
+
+        let modifiers = modifiers
+            .iter()
+            .map(|m| {
+                parse_modifier(m).ok_or_else(|| anyhow!("Unknown modifier `{m}` in `{chord}`"))
+            })
+            .try_fold(Modifiers::NONE, |all, m| m.map(|m| all | m))?;
+        let key = if let Some(key) = key.strip_prefix("phys:") {
+            parse_key(key).map(ChordKey::Physical)
+        } else {
+            parse_key(key.strip_prefix("mapped:").unwrap_or(key)).map(ChordKey::Mapped)
+        }
+        .ok_or_else(|| anyhow!("Unknown key `{key}` in `{chord}`"))?;
+
+        Ok(Self::new(key, modifiers))
+    }
+}
+
+fn parse_modifier(name: &str) -> Option<Modifiers> {
+    Some(match name.to_ascii_lowercase().as_str() {
+        "shift" => Modifiers::SHIFT,
+        "ctrl" | "control" => Modifiers::CTRL,
+        "alt" | "option" => Modifiers::ALT,
+        "super" | "cmd" | "command" | "win" => Modifiers::SUPER,
+        _ => return None,
+    })
+}
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+
+    Some(match name.to_ascii_lowercase().as_str() {
+        "plus" => KeyCode::Char('+'),
+        "minus" => KeyCode::Char('-'),
+        "space" => KeyCode::Char(' '),
+        "enter" | "return" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "escape" | "esc" => KeyCode::Escape,
+        "insert" => KeyCode::Insert,
+        "delete" => KeyCode::Delete,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "up" => KeyCode::UpArrow,
+        "down" => KeyCode::DownArrow,
+        "left" => KeyCode::LeftArrow,
+        "right" => KeyCode::RightArrow,
+        name => match name.strip_prefix('f')?.parse() {
+            Ok(n @ 1..=24) => KeyCode::Function(n),
+            _ => return None,
+        },
+    })
+}
+
+/// The key binding table.
+///
+/// Configured bindings are added to the platform defaults. A chord that is bound to `null` is
+/// unbound and passes through to the application.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "HashMap<String, Option<Action>>")]
+pub struct KeyBindings(HashMap<KeyChord, Action>);
+
+impl KeyBindings {
+    /// Find the action for a key press.
+    ///
+    /// `physical` is the key at the position of the pressed key on a US QWERTY layout. Physical
+    /// bindings take precedence. If the layout produces a non-ASCII character, mapped bindings
+    /// match the physical key, so that shortcuts work with Cyrillic or Greek layouts, for example.
+    pub fn action(
+        &self,
+        key: KeyCode,
+        physical: Option<KeyCode>,
+        modifiers: Modifiers,
+    ) -> Option<Action> {
+        let lookup = |key| self.0.get(&KeyChord::new(key, modifiers)).copied();
+
+        let non_latin = matches!(key, KeyCode::Char(c) if !c.is_ascii());
+        physical
+            .and_then(|physical| lookup(ChordKey::Physical(physical)))
+            .or_else(|| lookup(ChordKey::Mapped(key)))
+            .or_else(|| {
+                physical
+                    .filter(|_| non_latin)
+                    .and_then(|physical| lookup(ChordKey::Mapped(physical)))
+            })
+    }
+
+    fn platform_defaults() -> &'static [(&'static str, Action)] {
+        if cfg!(target_os = "macos") {
+            &[
+                ("Super+C", Action::Copy),
+                ("Super+Shift+C", Action::CopyHtml),
+                ("Super+V", Action::Paste),
+                ("Super+F", Action::Search),
+                ("Super+Shift+X", Action::CopyMode),
+                ("Super+Alt+Up", Action::ScrollLineUp),
+                ("Super+Alt+Down", Action::ScrollLineDown),
+                ("Shift+PageUp", Action::ScrollPageUp),
+                ("Shift+PageDown", Action::ScrollPageDown),
+                ("Super+Home", Action::ScrollToTop),
+                ("Super+End", Action::ScrollToBottom),
+                ("Super+Up", Action::ScrollToPreviousPrompt),
+                ("Super+Down", Action::ScrollToNextPrompt),
+                ("Super+=", Action::FontBigger),
+                ("Super+Plus", Action::FontBigger),
+                // Detail: Plus is typed with Shift on some layouts, like US QWERTY.
+                ("Super+Shift+Plus", Action::FontBigger),
+                ("Super+-", Action::FontSmaller),
+                ("Super+0", Action::FontReset),
+            ]
+        } else {
+            // Detail: Ctrl+Shift is used, because Ctrl alone is needed by applications and Super
+            // is reserved by window managers.
+            &[
+                ("Ctrl+Shift+C", Action::Copy),
+                ("Ctrl+Alt+Shift+C", Action::CopyHtml),
+                ("Ctrl+Shift+V", Action::Paste),
+                ("Ctrl+Shift+F", Action::Search),
+                ("Ctrl+Shift+X", Action::CopyMode),
+                ("Ctrl+Shift+Up", Action::ScrollLineUp),
+                ("Ctrl+Shift+Down", Action::ScrollLineDown),
+                ("Shift+PageUp", Action::ScrollPageUp),
+                ("Shift+PageDown", Action::ScrollPageDown),
+                ("Shift+Home", Action::ScrollToTop),
+                ("Shift+End", Action::ScrollToBottom),
+                ("Ctrl+Shift+PageUp", Action::ScrollToPreviousPrompt),
+                ("Ctrl+Shift+PageDown", Action::ScrollToNextPrompt),
+                ("Ctrl+=", Action::FontBigger),
+                ("Ctrl+Plus", Action::FontBigger),
+                // Detail: Plus is typed with Shift on some layouts, like US QWERTY.
+                ("Ctrl+Shift+Plus", Action::FontBigger),
+                ("Ctrl+-", Action::FontSmaller),
+                ("Ctrl+0", Action::FontReset),
+            ]
+        }
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self(
+            Self::platform_defaults()
+                .iter()
+                .map(|(chord, action)| {
+                    let chord =
+                        KeyChord::parse(chord).expect("Internal error: Invalid default key chord");
+                    (chord, *action)
+                })
+                .collect(),
+        )
+    }
+}
+
+impl TryFrom<HashMap<String, Option<Action>>> for KeyBindings {
+    type Error = anyhow::Error;
+
+    fn try_from(configured: HashMap<String, Option<Action>>) -> Result<Self> {
+        let mut bindings = Self::default();
+        for (chord, action) in configured {
+            let chord = KeyChord::parse(&chord)?;
+            match action {
+                Some(action) => bindings.0.insert(chord, action),
+                None => bindings.0.remove(&chord),
+            };
+        }
+        Ok(bindings)
+    }
+}
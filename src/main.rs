@@ -1,42 +1,55 @@
 use std::{
-    io::{self, ErrorKind},
+    collections::HashSet,
+    io::{self, ErrorKind, Write},
+    ops::Range,
     sync::{Arc, Weak},
     time::{Duration, Instant},
 };
 
 use anyhow::{Result, bail};
 use arboard::Clipboard;
+#[cfg(target_os = "linux")]
+use arboard::{GetExtLinux, LinuxClipboardKind, SetExtLinux};
 use derive_more::Debug;
-use log::{debug, info, trace, warn};
+use log::{debug, info, warn};
 use parking_lot::Mutex;
 use serde::Deserialize;
 use tokio::{pin, select, sync::Notify, task};
 use url::Url;
 use winit::{
-    event::{ElementState, MouseButton, MouseScrollDelta, TouchPhase},
+    event::{ElementState, Ime, MouseButton, MouseScrollDelta, TouchPhase},
+    keyboard::PhysicalKey,
     window::CursorIcon,
 };
 
 use portable_pty::{CommandBuilder, PtyPair, native_pty_system};
+use termwiz::input::{KeyboardEncoding, KittyKeyboardFlags};
 use wezterm_term::{
     KeyCode, KeyModifiers, Line, MouseEvent, StableRowIndex, Terminal, TerminalConfiguration, color,
 };
 
 use massive_applications::{InstanceContext, InstanceEvent, View, ViewEvent, ViewId};
 use massive_desktop::{Application, Desktop, DesktopEnvironment};
-use massive_geometry::{Color, Point, SizePx};
+use massive_geometry::{Color, Point, Rect, SizePx};
 use massive_input::{Event, EventManager, MouseGesture, Movement};
 use massive_renderer::FontWeight;
 use massive_shell::{ApplicationContext, shell};
+use massive_util::Progress;
 
+mod click_counter;
+mod config;
 mod input;
+mod key_bindings;
 mod range_ops;
 mod terminal;
 mod view_geometry;
 mod view_state;
 
 use crate::{
-    input::termwiz::{convert_modifiers, convert_mouse_event_from_view},
+    click_counter::ClickCounter,
+    config::Config,
+    input::termwiz::{AltKeys, WheelSteps, convert_modifiers, convert_mouse_event_from_view},
+    key_bindings::Action,
     range_ops::WithLength,
     terminal::*,
     view_geometry::{PixelPoint, ViewGeometry},
@@ -47,6 +60,8 @@ const TERMINAL_NAME: &str = "MassiveTerminal";
 /// Production: Extract from the build.
 const TERMINAL_VERSION: &str = "1.0";
 const DEFAULT_FONT_SIZE: f32 = 13.;
+/// The factor the font size changes with when it gets bigger or smaller.
+const FONT_SIZE_STEP: f32 = 1.1;
 const DEFAULT_TERMINAL_SIZE: (usize, usize) = (80 * 2, 24 * 2);
 const APPLICATION_NAME: &str = "Massive Terminal";
 
@@ -70,6 +85,9 @@ async fn terminal_instance(mut ctx: InstanceContext) -> Result<()> {
 struct MassiveTerminal {
     #[debug(skip)]
     pty_pair: PtyPair,
+    /// Writes the composed text to the pty, which the terminal would encode as keys.
+    #[debug(skip)]
+    pty_writer: PtyWriter,
 
     view: View,
 
@@ -77,22 +95,35 @@ struct MassiveTerminal {
 
     view_state: ViewState,
     presenter: TerminalPresenter,
-    // Architecture: This may belong into TerminalState or even TerminalView?
-    terminal_scroller: TerminalScroller,
 
     // User state
     //
     // Architecture: The movement tracking here and the selection tracking in the presenter should
     // probably be combined.
     selecting: Option<Movement>,
+    click_counter: ClickCounter,
+    /// The fractions of wheel steps not yet forwarded to the terminal.
+    wheel_steps: WheelSteps,
+    /// The Alt keys that are held down, for platforms that don't report them in the modifiers.
+    alt_keys: AltKeys,
+    /// The keys whose presses were sent as composed text. The application does not see their
+    /// releases.
+    text_keys: HashSet<PhysicalKey>,
+    /// The cursor area the input method was last told about.
+    reported_ime_cursor_area: Option<Rect>,
 
     #[debug(skip)]
     clipboard: Clipboard,
+
+    /// The font size in physical pixels the font is reset to.
+    default_font_size: f32,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 struct Parameters {
     command: Option<String>,
+    #[serde(default)]
+    config: Config,
 }
 
 enum RunMode {
@@ -143,28 +174,34 @@ impl MassiveTerminal {
         let mut cmd = CommandBuilder::new(&shell);
 
         // Deserialize parameters
-        if let Some(parameters) = ctx.parameters() {
-            let p: Parameters = serde_json::from_value(parameters.clone().into())?;
-            if let Some(command) = p.command {
-                cmd.arg("-c");
-                cmd.arg(command);
-            }
+        let parameters: Parameters = match ctx.parameters() {
+            Some(parameters) => serde_json::from_value(parameters.clone().into())?,
+            None => Parameters::default(),
+        };
+        if let Some(command) = parameters.command {
+            cmd.arg("-c");
+            cmd.arg(command);
         }
+        let config = Arc::new(parameters.config);
+        let click_counter =
+            ClickCounter::new(Duration::from_millis(config.multi_click_interval_ms));
 
         let _child = pty_pair.slave.spawn_command(cmd)?;
 
         // I don't how what and when anything blocks, so create two channels for writing and on for
         // reading from the pty. Send data to the pty by writing to the master
-        let writer = pty_pair.master.take_writer()?;
+        let pty_writer = PtyWriter(Arc::new(Mutex::new(pty_pair.master.take_writer()?)));
 
-        let configuration = MassiveTerminalConfiguration {};
+        let configuration = MassiveTerminalConfiguration {
+            config: config.clone(),
+        };
 
         let terminal = Terminal::new(
             terminal_geometry.wezterm_terminal_size(),
             Arc::new(configuration),
             TERMINAL_NAME,
             TERMINAL_VERSION,
-            writer,
+            Box::new(pty_writer.clone()),
         );
         let last_rendered_seq_no = terminal.current_seqno();
 
@@ -178,13 +215,11 @@ impl MassiveTerminal {
             fonts: fonts.clone(),
             font: terminal_font.clone(),
             location: view.location().clone(),
+            config,
         };
 
         let scene = view.scene();
 
-        let terminal_scroller =
-            TerminalScroller::new(scene, Duration::from_secs(1), Duration::from_secs(1));
-
         let presenter = TerminalPresenter::new(
             terminal_geometry,
             terminal,
@@ -195,16 +230,24 @@ impl MassiveTerminal {
 
         // Set initial title
         view.set_title(APPLICATION_NAME)?;
+        // Detail: Without this, the platform does not send any `Ime` events.
+        view.set_ime_allowed(true)?;
 
         Ok(Self {
             pty_pair,
+            pty_writer,
             view,
             event_manager: EventManager::default(),
             view_state: ViewState::new(view_geometry),
             presenter,
-            terminal_scroller,
             selecting: None,
+            click_counter,
+            wheel_steps: WheelSteps::default(),
+            alt_keys: AltKeys::default(),
+            text_keys: HashSet::new(),
+            reported_ime_cursor_area: None,
             clipboard: Clipboard::new()?,
+            default_font_size: font_size,
         })
     }
 
@@ -212,6 +255,10 @@ impl MassiveTerminal {
         &self.presenter.terminal
     }
 
+    fn config(&self) -> &Config {
+        &self.presenter.view().params.config
+    }
+
     async fn run(&mut self, ctx: &mut InstanceContext) -> Result<()> {
         let notify = Arc::new(Notify::new());
         // Read and parse output from the pty with reader
@@ -280,12 +327,6 @@ impl MassiveTerminal {
                 return Ok(());
             }
 
-            // Handle animations
-            if matches!(instance_event_opt, Some(InstanceEvent::ApplyAnimations)) {
-                trace!("Applying animations");
-                self.terminal_scroller.proceed();
-            }
-
             {
                 // Currently we need always apply view animations, otherwise the scroll locations
                 // are not in sync with the updated lines which results in flickering (or even
@@ -304,6 +345,19 @@ impl MassiveTerminal {
             // Render
             self.view.render()?;
 
+            // Keep scanning the buffer without waiting for the next event.
+            if self.presenter.search_in_progress() {
+                notify.notify_one();
+            }
+
+            // Move the input method's candidate window along with the cursor.
+            if let Some(area) = self.presenter.ime_cursor_area()
+                && self.reported_ime_cursor_area != Some(area)
+            {
+                self.view.set_ime_cursor_area(area)?;
+                self.reported_ime_cursor_area = Some(area);
+            }
+
             // Update mouse cursor shape.
             {
                 let cursor_icon = if self.presenter.is_hyperlink_underlined_under_mouse() {
@@ -349,6 +403,35 @@ impl MassiveTerminal {
         let view_pos_to_terminal_view =
             |p: massive_geometry::Point| -> Option<PixelPoint> { Some((p.x, p.y).into()) };
 
+        // The scrollbar takes precedence over selecting and mouse reporting.
+        let scrollbar_pos = ev.pos().and_then(view_pos_to_terminal_view);
+        let scrollbar_hit = match view_event {
+            ViewEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } if self.selecting.is_none() => {
+                scrollbar_pos.is_some_and(|pos| self.presenter.scrollbar_press(pos))
+            }
+            ViewEvent::MouseInput {
+                state: ElementState::Released,
+                button: MouseButton::Left,
+                ..
+            } => self.presenter.scrollbar_release(),
+            ViewEvent::CursorMoved { .. } => {
+                scrollbar_pos.is_some_and(|pos| self.presenter.scrollbar_drag_to(pos))
+            }
+            _ => false,
+        };
+        if scrollbar_hit {
+            return Ok(());
+        }
+
+        // Set if a selection was completed with the mouse.
+        let mut selection_committed = false;
+
+        let bypass_modifier = self.config().mouse_reporting_bypass_modifier;
+
         {
             let presenter = &mut self.presenter;
             let view_geometry = presenter.view_geometry();
@@ -384,23 +467,101 @@ impl MassiveTerminal {
                 }
             }
 
+            let bypassing_mouse_reporting =
+                terminal.is_mouse_grabbed() && bypass_modifier.is_pressed(modifiers.state());
+            // Shift+click and Shift+drag extend the selection, unless Shift is used to bypass mouse
+            // reporting.
+            let extend_selection = modifiers.state().shift_key()
+                && !(bypassing_mouse_reporting && bypass_modifier == config::BypassModifier::Shift);
+
+            let mouse_event = matches!(
+                view_event,
+                ViewEvent::CursorMoved { .. }
+                    | ViewEvent::MouseInput { .. }
+                    | ViewEvent::MouseWheel { .. }
+            );
+
             // Process events that need to be forwarded to the terminal when mouse reporting is on.
-            if terminal.is_mouse_grabbed() {
-                Self::may_forward_event_to_terminal(
+            //
+            // Detail: A selection that was started while bypassing continues when the modifier is
+            // released.
+            if mouse_event
+                && terminal.is_mouse_grabbed()
+                && !bypassing_mouse_reporting
+                && self.selecting.is_none()
+            {
+                let button_pressed = Self::may_forward_event_to_terminal(
                     &ev,
                     &mut terminal,
+                    &mut self.wheel_steps,
                     &view_geometry,
                     view_pos_to_terminal_view,
                 );
 
-                self.presenter.selection_clear();
-                self.selecting = None;
+                // A click the application receives removes the selection.
+                if button_pressed {
+                    self.presenter.selection_clear();
+                }
             } else {
+                // A middle click pastes the PRIMARY selection.
+                #[cfg(target_os = "linux")]
+                if let ViewEvent::MouseInput {
+                    state: ElementState::Pressed,
+                    button: MouseButton::Middle,
+                    ..
+                } = view_event
+                {
+                    match Self::primary_selection_text(&mut self.clipboard) {
+                        Ok(text) if !text.is_empty() => {
+                            terminal.send_paste(&text)?;
+                            self.presenter.enable_autoscroll();
+                        }
+                        Ok(_) => {}
+                        Err(e) => warn!("Reading the PRIMARY selection failed: {e:?}"),
+                    }
+                }
+
                 // Process selecting user state
 
+                // Zero if this is not a press of the left mouse button.
+                let click_count = match view_event {
+                    ViewEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Left,
+                        ..
+                    } => ev.pos().map_or(0, |pos| {
+                        self.click_counter.press(now, pos, min_movement_distance)
+                    }),
+                    _ => 0,
+                };
+
                 match &mut self.selecting {
+                    // Double clicks select words, triple clicks lines, and quadruple clicks the
+                    // semantic zone.
+                    None if click_count > 1 => {
+                        if let Some(hit) = ev.pos().and_then(view_pos_to_terminal_view) {
+                            match click_count {
+                                2 => self.presenter.selection_begin_word(hit, &terminal),
+                                3 => self.presenter.selection_begin(SelectionMode::Line, hit),
+                                _ => self.presenter.selection_begin_zone(hit, &mut terminal),
+                            }
+                            if self.presenter.selection_can_progress() {
+                                self.selecting = ev.track_movement();
+                            }
+                        }
+                    }
                     None => match ev.detect_mouse_gesture(MouseButton::Left, min_movement_distance)
                     {
+                        // Shift+click extends the selection. Without one, it is a normal click.
+                        Some(MouseGesture::Clicked(point))
+                            if extend_selection
+                                && view_pos_to_terminal_view(point)
+                                    .is_some_and(|hit| self.presenter.selection_extend(hit)) =>
+                        {
+                            self.presenter
+                                .selection_progress(self.view.scene(), Progress::Commit);
+                            selection_committed = true;
+                        }
                         // WezTerm reacts on Click, macOS term on Clicked.
                         Some(MouseGesture::Clicked(point)) => {
                             if let Some(view_px) = view_pos_to_terminal_view(point) {
@@ -414,26 +575,20 @@ impl MassiveTerminal {
                                 }
                             }
 
-                            presenter.selection_clear();
-                        }
-                        Some(MouseGesture::DoubleClick(point)) => {
-                            if let Some(hit) = view_pos_to_terminal_view(point) {
-                                // Architecture: May create a MouseGesture::TripleClick instead?
-                                let mode = if self.presenter.selection_in_word_mode_and_selected() {
-                                    // This implicitly detects a triple click and then uses line selection.
-                                    SelectionMode::Line
-                                } else {
-                                    SelectionMode::Word
-                                };
-
-                                self.presenter.selection_begin(mode, hit);
-                                self.selecting = Some(ev.track_movement()
-                                    .expect("Internal error: double click gesture triggered without a mouse button event"));
-                            }
+                            self.presenter.selection_clear();
                         }
                         Some(MouseGesture::Movement(movement)) => {
                             if let Some(hit) = view_pos_to_terminal_view(movement.from) {
-                                self.presenter.selection_begin(SelectionMode::Cell, hit);
+                                let extended =
+                                    extend_selection && self.presenter.selection_extend(hit);
+                                if !extended {
+                                    let mode = if modifiers.state().alt_key() {
+                                        SelectionMode::Rectangular
+                                    } else {
+                                        SelectionMode::Cell
+                                    };
+                                    self.presenter.selection_begin(mode, hit);
+                                }
                             }
                             self.selecting = Some(movement);
                         }
@@ -442,6 +597,7 @@ impl MassiveTerminal {
                     Some(movement) => {
                         if let Some(progress) = movement.track_to(&ev) {
                             let progress = progress.try_map_or_cancel(view_pos_to_terminal_view);
+                            selection_committed = matches!(progress, Progress::Commit);
 
                             self.presenter
                                 .selection_progress(self.view.scene(), progress);
@@ -454,6 +610,13 @@ impl MassiveTerminal {
             }
         }
 
+        if selection_committed
+            && self.presenter.view().params.config.copy_on_select
+            && let Err(e) = self.copy_to_primary_selection()
+        {
+            warn!("Copying the selection to the PRIMARY selection failed: {e:?}");
+        }
+
         // Process remaining events
         match view_event {
             ViewEvent::Resized(size) => {
@@ -463,6 +626,10 @@ impl MassiveTerminal {
                 // Architecture: Should we track the focused state of the window in the EventAggregator?
                 // Architecture: Move this to the part where the terminal is locked above.
                 self.view_state.focused = *focused;
+                if !*focused {
+                    self.alt_keys.reset();
+                    self.text_keys.clear();
+                }
                 self.terminal().lock().focus_changed(*focused);
             }
             ViewEvent::MouseWheel {
@@ -470,38 +637,85 @@ impl MassiveTerminal {
                 phase: TouchPhase::Moved,
                 ..
             } => {
-                let delta_px = match delta {
+                if self.alternate_scroll(*delta)? {
+                    return Ok(());
+                }
+
+                match delta {
                     MouseScrollDelta::LineDelta(_, delta) => {
-                        (*delta as f64) * self.presenter.geometry().line_height_px() as f64
+                        let delta_px =
+                            (*delta as f64) * self.presenter.geometry().line_height_px() as f64;
+                        self.presenter.scroll_delta_px(-delta_px)
                     }
-                    MouseScrollDelta::PixelDelta(physical_position) => physical_position.y,
-                };
-
-                self.presenter.scroll_delta_px(-delta_px)
+                    // Pixel deltas come from trackpads, which scroll kinetically.
+                    MouseScrollDelta::PixelDelta(physical_position) => self
+                        .presenter
+                        .scroll_track_px(self.view.scene(), -physical_position.y, now),
+                }
             }
+            ViewEvent::MouseWheel {
+                phase: TouchPhase::Started,
+                ..
+            } => self.presenter.scroll_begin(now),
+            ViewEvent::MouseWheel {
+                phase: TouchPhase::Ended | TouchPhase::Cancelled,
+                ..
+            } => self.presenter.scroll_release(now),
             ViewEvent::KeyboardInput { event, .. } => {
-                if let Some((key, key_modifiers)) =
-                    input::termwiz::convert_key_event(event, modifiers)
+                self.alt_keys.track(event);
+                let alt_as_meta = self.config().alt_as_meta;
+                let keypad_codes = Self::encodes_keypad_keys(&self.terminal().lock());
+                let converted = input::termwiz::convert_key_event(
+                    event,
+                    modifiers,
+                    &self.alt_keys,
+                    alt_as_meta,
+                    keypad_codes,
+                );
+                let composed = converted.and_then(|(_, key_modifiers)| {
+                    input::termwiz::composed_text(event, key_modifiers)
+                });
+
+                if event.state == ElementState::Pressed
+                    && let Some(text) = composed
                 {
+                    self.text_keys.insert(event.physical_key);
+                    self.text_committed(text)?;
+                } else if event.state == ElementState::Released
+                    && self.text_keys.remove(&event.physical_key)
+                {
+                    // The application has seen the press as text, not as a key.
+                } else if let Some((key, key_modifiers)) = converted {
+                    let physical = input::termwiz::convert_physical_key(&event.physical_key);
+                    let action = self
+                        .config()
+                        .key_bindings
+                        .action(key, physical, key_modifiers);
                     match event.state {
-                        ElementState::Pressed => match key {
-                            KeyCode::Char('c') if key_modifiers == KeyModifiers::SUPER => {
-                                self.copy()?;
-                            }
-                            KeyCode::Char('v') if key_modifiers == KeyModifiers::SUPER => {
-                                self.paste()?
-                            }
-                            _ => {
-                                self.terminal().lock().key_down(key, key_modifiers)?;
-                                self.presenter.enable_autoscroll();
-                            }
-                        },
+                        ElementState::Pressed => self.key_pressed(key, key_modifiers, action)?,
+                        // Keys are not forwarded while the search or copy mode consume them.
+                        ElementState::Released
+                            if self.presenter.search().is_some()
+                                || self.presenter.copy_mode().is_some() => {}
+                        // The application has not seen the press of a bound key.
+                        ElementState::Released if action.is_some() => {}
+                        ElementState::Released
+                            if key.is_modifier()
+                                && !Self::reports_modifier_keys(&self.terminal().lock()) => {}
                         ElementState::Released => {
                             self.terminal().lock().key_up(key, key_modifiers)?;
                         }
                     }
                 }
             }
+            ViewEvent::Ime(ime) => match ime {
+                Ime::Preedit(text, _cursor) => self.presenter.set_preedit(Some(text.clone())),
+                Ime::Commit(text) => {
+                    self.presenter.set_preedit(None);
+                    self.text_committed(text)?;
+                }
+                Ime::Enabled | Ime::Disabled => self.presenter.set_preedit(None),
+            },
             ViewEvent::CloseRequested => {
                 // Desktop handles close requests
             }
@@ -510,51 +724,105 @@ impl MassiveTerminal {
         Ok(())
     }
 
+    /// Returns `true` if the press of a button was forwarded.
     // Architecture: Is the presenter responsible for this?
     fn may_forward_event_to_terminal(
         ev: &Event<ViewEvent>,
         terminal: &mut Terminal,
+        wheel_steps: &mut WheelSteps,
         geometry: &TerminalViewGeometry,
         map_to_view: impl Fn(Point) -> Option<PixelPoint>,
-    ) {
+    ) -> bool {
         debug_assert!(terminal.is_mouse_grabbed());
 
-        let Some((kind, button, point)) = convert_mouse_event_from_view(ev) else {
-            return;
-        };
+        let mut button_pressed = false;
 
-        // Performance: Shouldn't we check if the pos is on the view before converting the mouse event?
-        let Some(point_on_view) = map_to_view(point) else {
-            return;
-        };
+        let cell_size_px = geometry.terminal.cell_size_px;
+        let cell_size_px = (cell_size_px.width as f64, cell_size_px.height as f64);
 
-        let cell_pos = geometry.hit_test_cell(point_on_view);
+        for (kind, button, point) in convert_mouse_event_from_view(ev, wheel_steps, cell_size_px) {
+            // Performance: Shouldn't we check if the pos is on the view before converting the mouse event?
+            let Some(point_on_view) = map_to_view(point) else {
+                continue;
+            };
 
-        let stable_top = terminal.screen().visible_row_to_stable_row(0);
-        let visible_row = cell_pos.row - stable_top;
+            let cell_pos = geometry.hit_test_cell(point_on_view);
 
-        let Some(column): Option<usize> = cell_pos.column.try_into().ok() else {
-            return;
-        };
+            let stable_top = terminal.screen().visible_row_to_stable_row(0);
+            let visible_row = cell_pos.row - stable_top;
 
-        let Some(discrete_point) = point_on_view.round().try_cast() else {
-            return;
-        };
+            let Some(column): Option<usize> = cell_pos.column.try_into().ok() else {
+                continue;
+            };
+
+            // Detail: wezterm_term adds the offsets to the cell's origin for SGR pixel reporting
+            // (mode 1016), so they are relative to the cell, not to the view.
+            let (x_pixel_offset, y_pixel_offset) = geometry.cell_pixel_offset(point_on_view);
+
+            let event = MouseEvent {
+                kind,
+                x: column,
+                y: visible_row as _,
+                x_pixel_offset: x_pixel_offset as _,
+                y_pixel_offset: y_pixel_offset as _,
+                button,
+                modifiers: convert_modifiers(ev.device_states().keyboard_modifiers()),
+            };
+
+            debug!("Sending mouse event to terminal {event:?}");
+            match terminal.mouse_event(event) {
+                Ok(()) => {
+                    button_pressed |= matches!(
+                        (kind, button),
+                        (
+                            wezterm_term::MouseEventKind::Press,
+                            wezterm_term::MouseButton::Left
+                                | wezterm_term::MouseButton::Right
+                                | wezterm_term::MouseButton::Middle
+                        )
+                    );
+                }
+                Err(e) => warn!("Sending mouse event to terminal failed: {e:?}"),
+            }
+        }
 
-        let event = MouseEvent {
-            kind,
-            x: column,
-            y: visible_row as _,
-            x_pixel_offset: discrete_point.x,
-            y_pixel_offset: discrete_point.y,
-            button,
-            modifiers: convert_modifiers(ev.device_states().keyboard_modifiers()),
+        button_pressed
+    }
+
+    /// Send a wheel delta as Up or Down keys if the alternate screen is active and the mouse is not
+    /// grabbed.
+    ///
+    /// Returns `true` if the delta was consumed.
+    ///
+    /// Detail: This is xterm's alternate scroll mode (DECSET 1007), which is always on, because
+    /// wezterm_term does not track it.
+    fn alternate_scroll(&mut self, delta: MouseScrollDelta) -> Result<bool> {
+        let lines_per_step = self.config().alternate_screen_wheel_lines;
+        let terminal = self.terminal().clone();
+        let mut terminal = terminal.lock();
+        if lines_per_step == 0 || !terminal.is_alt_screen_active() || terminal.is_mouse_grabbed() {
+            return Ok(false);
+        }
+
+        let cell_size_px = self.presenter.geometry().cell_size_px;
+        let cell_size_px = (cell_size_px.width as f64, cell_size_px.height as f64);
+        let (_, steps) = self.wheel_steps.add_steps(delta, cell_size_px);
+        // Pixel deltas are already converted to lines.
+        let lines_per_step = match delta {
+            MouseScrollDelta::LineDelta(..) => lines_per_step,
+            MouseScrollDelta::PixelDelta(_) => 1,
         };
 
-        debug!("Sending mouse event to terminal {event:?}");
-        if let Err(e) = terminal.mouse_event(event) {
-            warn!("Sending mouse event to terminal failed: {e:?}")
+        // Detail: Positive deltas scroll the view up, see the scrollback handling of the wheel.
+        let key = if steps > 0.0 {
+            KeyCode::UpArrow
+        } else {
+            KeyCode::DownArrow
+        };
+        for _ in 0..steps.abs() as usize * lines_per_step {
+            terminal.key_down(key, KeyModifiers::NONE)?;
         }
+        Ok(true)
     }
 
     fn resize(&mut self, new_view_size_px: SizePx) -> Result<()> {
@@ -574,6 +842,157 @@ impl MassiveTerminal {
     }
 }
 
+// Keyboard
+
+impl MassiveTerminal {
+    /// Modifier keys are reported to applications that enabled the kitty keyboard protocol's
+    /// "report all keys as escape codes" enhancement only.
+    fn reports_modifier_keys(terminal: &Terminal) -> bool {
+        matches!(
+            terminal.get_keyboard_encoding(),
+            KeyboardEncoding::Kitty(flags)
+                if flags.contains(KittyKeyboardFlags::REPORT_ALL_KEYS_AS_ESCAPE_CODES)
+        )
+    }
+
+    /// Keypad digits and operators are sent as keypad keys to applications that enabled the kitty
+    /// keyboard protocol, which encodes them apart from the main keyboard.
+    ///
+    /// Feature: Application keypad mode (DECKPAM). wezterm_term does not expose the mode and
+    /// termwiz's legacy encoder encodes the keypad digits like the cursor keys, so without the
+    /// kitty keyboard protocol, the keys send their characters.
+    fn encodes_keypad_keys(terminal: &Terminal) -> bool {
+        matches!(
+            terminal.get_keyboard_encoding(),
+            KeyboardEncoding::Kitty(flags)
+                if flags.intersects(
+                    KittyKeyboardFlags::DISAMBIGUATE_ESCAPE_CODES
+                        | KittyKeyboardFlags::REPORT_ALL_KEYS_AS_ESCAPE_CODES
+                )
+        )
+    }
+
+    /// Process a key press, `action` is the action the key is bound to.
+    fn key_pressed(
+        &mut self,
+        key: KeyCode,
+        modifiers: KeyModifiers,
+        action: Option<Action>,
+    ) -> Result<()> {
+        let consuming = self.presenter.search().is_some() || self.presenter.copy_mode().is_some();
+        // Modifier keys are only reported to applications and don't scroll to the bottom.
+        if key.is_modifier() {
+            let mut terminal = self.terminal().lock();
+            if !consuming && Self::reports_modifier_keys(&terminal) {
+                terminal.key_down(key, modifiers)?;
+            }
+            return Ok(());
+        }
+
+        // Bound keys like copy pass through the search and the copy mode.
+        //
+        // Detail: The search and the copy mode don't distinguish between left and right
+        // modifiers, nor between the keypad and the main keyboard.
+        let unpositioned = modifiers.remove_positional_mods();
+        let typed = input::termwiz::keypad_char(key).map_or(key, KeyCode::Char);
+        if action.is_none()
+            && (self.process_search_key(typed, unpositioned)?
+                || self.process_copy_mode_key(typed, unpositioned)?)
+        {
+            return Ok(());
+        }
+
+        match action {
+            Some(action) => self.perform_action(action)?,
+            None => {
+                self.terminal().lock().key_down(key, modifiers)?;
+                self.presenter.enable_autoscroll();
+            }
+        }
+        Ok(())
+    }
+
+    /// Process text composed by the input method, dead keys, or compose sequences.
+    ///
+    /// Detail: Like wezterm, the text is written as UTF-8 to the pty. Sent through `key_down()`, it
+    /// would be encoded as one key event per character when the kitty keyboard protocol is on.
+    fn text_committed(&mut self, text: &str) -> Result<()> {
+        if self.presenter.search().is_some() {
+            for c in text.chars() {
+                self.process_search_key(KeyCode::Char(c), KeyModifiers::NONE)?;
+            }
+            return Ok(());
+        }
+        // The copy mode consumes the text.
+        if self.presenter.copy_mode().is_some() {
+            return Ok(());
+        }
+
+        self.pty_writer.write_all(text.as_bytes())?;
+        self.pty_writer.flush()?;
+        self.presenter.enable_autoscroll();
+        Ok(())
+    }
+}
+
+// Actions
+
+impl MassiveTerminal {
+    fn perform_action(&mut self, action: Action) -> Result<()> {
+        match action {
+            Action::Copy => self.copy()?,
+            Action::CopyHtml => self.copy_html()?,
+            Action::Paste => self.paste()?,
+            Action::Search => {
+                self.presenter.search_begin();
+                self.update_title()?;
+            }
+            Action::CopyMode => {
+                self.presenter.copy_mode_begin();
+                self.update_title()?;
+            }
+            Action::ScrollLineUp => self.scroll_lines(-1.0),
+            Action::ScrollLineDown => self.scroll_lines(1.0),
+            Action::ScrollPageUp => self.scroll_pages(-1.0),
+            Action::ScrollPageDown => self.scroll_pages(1.0),
+            Action::ScrollToTop => self.presenter.scroll_to_top(),
+            Action::ScrollToBottom => self.presenter.scroll_to_bottom(),
+            Action::ScrollToPreviousPrompt => self.presenter.scroll_to_previous_prompt()?,
+            Action::ScrollToNextPrompt => self.presenter.scroll_to_next_prompt()?,
+            Action::FontBigger => self.set_font_size(self.font_size() * FONT_SIZE_STEP)?,
+            Action::FontSmaller => self.set_font_size(self.font_size() / FONT_SIZE_STEP)?,
+            Action::FontReset => self.set_font_size(self.default_font_size)?,
+        }
+        Ok(())
+    }
+
+    fn scroll_lines(&mut self, lines: f64) {
+        let line_px = self.presenter.geometry().line_height_px() as f64;
+        self.presenter.scroll_delta_px(lines * line_px);
+    }
+
+    fn scroll_pages(&mut self, pages: f64) {
+        let page_px = self.presenter.geometry().size_px().height as f64;
+        self.presenter.scroll_delta_px(pages * page_px);
+    }
+
+    fn font_size(&self) -> f32 {
+        self.presenter.view().params.font.size
+    }
+
+    fn set_font_size(&mut self, size: f32) -> Result<()> {
+        let size = size.clamp(self.default_font_size / 4., self.default_font_size * 4.);
+        let font =
+            TerminalFont::from_cosmic_text(self.presenter.view().params.font.font.clone(), size)?;
+        if self.presenter.set_font(font, self.view.scene())? {
+            self.pty_pair
+                .master
+                .resize(self.presenter.geometry().pty_size())?;
+        }
+        Ok(())
+    }
+}
+
 // Clipboard
 
 impl MassiveTerminal {
@@ -586,6 +1005,45 @@ impl MassiveTerminal {
         Ok(())
     }
 
+    /// Copy the selection as HTML with its colors and attributes, and as plain text for targets
+    /// that don't accept HTML.
+    fn copy_html(&mut self) -> Result<()> {
+        let text = self.selected_text();
+        if !text.is_empty() {
+            let html = self.selected_html();
+            // Robustness: May not fail if this returns an error.
+            self.clipboard.set_html(html, Some(text))?;
+        }
+        Ok(())
+    }
+
+    /// Copy the selection to the PRIMARY selection, or to the clipboard if there is none.
+    fn copy_to_primary_selection(&mut self) -> Result<()> {
+        let text = self.selected_text();
+        if !text.is_empty() {
+            #[cfg(target_os = "linux")]
+            self.clipboard
+                .set()
+                .clipboard(LinuxClipboardKind::Primary)
+                .text(text)?;
+            #[cfg(not(target_os = "linux"))]
+            self.clipboard.set_text(text)?;
+        }
+        Ok(())
+    }
+
+    /// The text of the PRIMARY selection.
+    ///
+    /// Detail: Takes the clipboard only, because it's called while mouse events are processed.
+    #[cfg(target_os = "linux")]
+    fn primary_selection_text(clipboard: &mut Clipboard) -> Result<String> {
+        let text = clipboard
+            .get()
+            .clipboard(LinuxClipboardKind::Primary)
+            .text()?;
+        Ok(text)
+    }
+
     fn paste(&mut self) -> Result<()> {
         // Robustness: May not fail if this returns an error?
         let text = self.clipboard.get_text()?;
@@ -597,6 +1055,101 @@ impl MassiveTerminal {
     }
 }
 
+// Search
+
+impl MassiveTerminal {
+    /// Process a key while searching.
+    ///
+    /// Like in wezterm's search, Enter and Up select the previous match, because the search starts
+    /// at the bottom and goes up the scrollback. Shift+Enter and Down select the next match.
+    ///
+    /// Returns `true` if the key was consumed by the search.
+    fn process_search_key(&mut self, key: KeyCode, modifiers: KeyModifiers) -> Result<bool> {
+        let Some(search) = self.presenter.search_mut() else {
+            return Ok(false);
+        };
+
+        match key {
+            KeyCode::Escape => self.presenter.search_end(),
+            KeyCode::Enter if modifiers == KeyModifiers::SHIFT => search.next(),
+            KeyCode::Enter | KeyCode::UpArrow => search.previous(),
+            KeyCode::DownArrow => search.next(),
+            KeyCode::Char('r') if modifiers == KeyModifiers::CTRL => search.cycle_kind(),
+            KeyCode::Backspace => search.pop_char(),
+            KeyCode::Char(c) if (modifiers - KeyModifiers::SHIFT).is_empty() => search.push_char(c),
+            _ => {}
+        }
+
+        // In copy mode, the cursor follows the current match.
+        if let Some(current) = self.presenter.search().and_then(|s| s.current()) {
+            self.presenter.copy_mode_set_cursor(*current.start());
+        }
+
+        self.update_title()?;
+        Ok(true)
+    }
+}
+
+// Copy Mode
+
+impl MassiveTerminal {
+    /// Process a key in copy mode.
+    ///
+    /// Returns `true` if the key was consumed by the copy mode.
+    fn process_copy_mode_key(&mut self, key: KeyCode, modifiers: KeyModifiers) -> Result<bool> {
+        let Some(copy_mode) = self.presenter.copy_mode_mut() else {
+            return Ok(false);
+        };
+
+        let selecting = copy_mode.is_selecting();
+        if let Some(motion) = copy_mode.key_motion(key, modifiers) {
+            self.presenter.copy_mode_move(motion);
+            self.update_title()?;
+            return Ok(true);
+        }
+
+        match key {
+            KeyCode::Char('v') if modifiers == KeyModifiers::CTRL => self
+                .presenter
+                .copy_mode_toggle_selection(SelectionMode::Rectangular),
+            _ if !CopyMode::is_unmodified(modifiers) => {}
+            KeyCode::Char('v') => self
+                .presenter
+                .copy_mode_toggle_selection(SelectionMode::Cell),
+            KeyCode::Char('V') => self
+                .presenter
+                .copy_mode_toggle_selection(SelectionMode::Line),
+            KeyCode::Char('y') => {
+                self.copy()?;
+                self.presenter.copy_mode_end();
+            }
+            KeyCode::Char('/') => self.presenter.search_begin(),
+            KeyCode::Escape if selecting => self.presenter.copy_mode_stop_selecting(),
+            KeyCode::Escape | KeyCode::Char('q') => self.presenter.copy_mode_end(),
+            _ => {}
+        }
+
+        self.update_title()?;
+        Ok(true)
+    }
+}
+
+// Title
+
+impl MassiveTerminal {
+    fn update_title(&self) -> Result<()> {
+        let title = if let Some(search) = self.presenter.search() {
+            format!("Search ({}): {}", search.kind().name(), search.query())
+        } else if self.presenter.copy_mode().is_some() {
+            format!("{APPLICATION_NAME} (Copy Mode)")
+        } else {
+            APPLICATION_NAME.to_string()
+        };
+        self.view.set_title(&title)?;
+        Ok(())
+    }
+}
+
 // Selection
 
 impl MassiveTerminal {
@@ -609,10 +1162,26 @@ impl MassiveTerminal {
     /// Returns the selected text
     pub fn selected_text(&self) -> String {
         let mut s = String::new();
-        // Feature: Rectangular selection.
-        let rectangular = false;
+        self.write_selection(&mut s);
+        s
+    }
+
+    /// Returns the selection as a HTML document.
+    pub fn selected_html(&self) -> String {
+        let view = self.presenter.view();
+        let mut writer = HtmlSelectionWriter {
+            view,
+            reverse_video: self.terminal().lock().get_reverse_video(),
+            html: String::new(),
+        };
+        self.write_selection(&mut writer);
+        view.html_document(&writer.html)
+    }
+
+    fn write_selection(&self, s: &mut impl SelectionWriter) {
+        let rectangular = self.presenter.selection_mode() == Some(SelectionMode::Rectangular);
         let Some(sel) = self.presenter.selected_range() else {
-            return s;
+            return;
         };
         let mut last_was_wrapped = false;
         let first_row = sel.stable_rows().start;
@@ -621,24 +1190,23 @@ impl MassiveTerminal {
         let terminal = self.terminal().lock();
 
         for line in get_logical_lines(&terminal, sel.stable_rows()) {
-            if !s.is_empty() && !last_was_wrapped {
-                s.push('\n');
+            if !s.is_empty() && !last_was_wrapped && !rectangular {
+                s.push_line_break();
             }
             let last_idx = line.physical_lines.len().saturating_sub(1);
             for (idx, phys) in line.physical_lines.iter().enumerate() {
                 let this_row = line.first_row + idx as StableRowIndex;
                 if this_row >= first_row && this_row < last_row {
+                    // Every row of a rectangular selection is copied as a separate line.
+                    if rectangular && this_row > first_row {
+                        s.push_line_break();
+                    }
                     let last_phys_idx = phys.len().saturating_sub(1);
                     let cols = sel.cols_for_row(this_row, rectangular);
                     let last_col_idx = cols.end.saturating_sub(1).min(last_phys_idx);
-                    let col_span = phys.columns_as_str(cols);
                     // Only trim trailing whitespace if we are the last line
                     // in a wrapped sequence
-                    if idx == last_idx {
-                        s.push_str(col_span.trim_end());
-                    } else {
-                        s.push_str(&col_span);
-                    }
+                    s.push_columns(phys, cols, idx == last_idx || rectangular);
 
                     last_was_wrapped = last_col_idx == last_phys_idx
                         && phys
@@ -648,19 +1216,95 @@ impl MassiveTerminal {
                 }
             }
         }
+    }
+}
 
-        s
+/// The output of [`MassiveTerminal::write_selection`].
+trait SelectionWriter {
+    fn is_empty(&self) -> bool;
+    fn push_line_break(&mut self);
+    fn push_columns(&mut self, line: &Line, columns: Range<usize>, trim_end: bool);
+}
+
+impl SelectionWriter for String {
+    fn is_empty(&self) -> bool {
+        str::is_empty(self)
+    }
+
+    fn push_line_break(&mut self) {
+        self.push('\n');
+    }
+
+    fn push_columns(&mut self, line: &Line, columns: Range<usize>, trim_end: bool) {
+        let col_span = line.columns_as_str(columns);
+        if trim_end {
+            self.push_str(col_span.trim_end());
+        } else {
+            self.push_str(&col_span);
+        }
+    }
+}
+
+struct HtmlSelectionWriter<'a> {
+    view: &'a TerminalView,
+    reverse_video: bool,
+    html: String,
+}
+
+impl SelectionWriter for HtmlSelectionWriter<'_> {
+    fn is_empty(&self) -> bool {
+        self.html.is_empty()
+    }
+
+    fn push_line_break(&mut self) {
+        self.html.push('\n');
+    }
+
+    fn push_columns(&mut self, line: &Line, columns: Range<usize>, trim_end: bool) {
+        self.view
+            .cells_to_html(line, columns, trim_end, self.reverse_video, &mut self.html);
     }
 }
 
 #[derive(Debug)]
-struct MassiveTerminalConfiguration {}
+struct MassiveTerminalConfiguration {
+    config: Arc<Config>,
+}
 
 impl TerminalConfiguration for MassiveTerminalConfiguration {
     fn color_palette(&self) -> color::ColorPalette {
         // Production: Review.
         color::ColorPalette::default()
     }
+
+    // Detail: Sixel and iTerm2 inline images are always enabled in wezterm_term.
+    fn enable_kitty_graphics(&self) -> bool {
+        true
+    }
+
+    // Detail: The terminal tracks the enhancement flags the application pushes and pops, and
+    // encodes key presses and releases accordingly.
+    fn enable_kitty_keyboard(&self) -> bool {
+        self.config.enable_kitty_keyboard
+    }
+
+    fn enable_csi_u_key_encoding(&self) -> bool {
+        self.config.enable_csi_u_key_encoding
+    }
+}
+
+/// The writer to the pty, shared by the terminal and the composed text.
+#[derive(Clone)]
+struct PtyWriter(Arc<Mutex<Box<dyn io::Write + Send>>>);
+
+impl io::Write for PtyWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.lock().flush()
+    }
 }
 
 // Detail: pass terminal as a Weak reference handle, because otherwise we would lock the terminal in
@@ -713,26 +1357,3 @@ fn open_file_http_or_mailto_url(uri: &str) -> Result<()> {
         _ => bail!("Unsupported URI scheme: `{scheme}` in `{uri}`"),
     }
 }
-
-mod config {
-    use std::sync::LazyLock;
-
-    use termwiz::hyperlink::{self, Rule};
-
-    pub static DEFAULT_HYPERLINK_RULES: LazyLock<Vec<Rule>> = LazyLock::new(|| {
-        vec![
-            // First handle URLs wrapped with punctuation (i.e. brackets)
-            // e.g. [http://foo] (http://foo) <http://foo>
-            Rule::with_highlight(r"\((\w+://\S+)\)", "$1", 1).unwrap(),
-            Rule::with_highlight(r"\[(\w+://\S+)\]", "$1", 1).unwrap(),
-            Rule::with_highlight(r"<(\w+://\S+)>", "$1", 1).unwrap(),
-            // Then handle URLs not wrapped in brackets that
-            // 1) have a balanced ending parenthesis or
-            Rule::new(hyperlink::CLOSING_PARENTHESIS_HYPERLINK_PATTERN, "$0").unwrap(),
-            // 2) include terminating _, / or - characters, if any
-            Rule::new(hyperlink::GENERIC_HYPERLINK_PATTERN, "$0").unwrap(),
-            // implicit mailto link
-            Rule::new(r"\b\w+@[\w-]+(\.[\w-]+)+\b", "mailto:$0").unwrap(),
-        ]
-    });
-}
@@ -0,0 +1,93 @@
+use std::ops::Range;
+
+use wezterm_bidi::{BidiContext, ParagraphDirectionHint};
+use wezterm_term::Line;
+
+/// The visual order of the columns of a line.
+///
+/// Columns outside of the line's cells keep their position.
+#[derive(Debug, PartialEq, Eq)]
+pub struct VisualOrder {
+    /// The visual column of each logical column.
+    visual_columns: Vec<usize>,
+    /// The logical column shown at each visual column.
+    logical_columns: Vec<usize>,
+}
+
+impl VisualOrder {
+    /// Resolve the visual order of a line.
+    ///
+    /// Returns `None` if the visual order matches the logical order.
+    pub fn from_line(line: &Line, hint: ParagraphDirectionHint) -> Option<Self> {
+        // Every cell is represented by its first character. Combining characters don't influence
+        // the resolved levels.
+        let cells: Vec<_> = line
+            .visible_cells()
+            .map(|cell| {
+                let ch = cell.str().chars().next().unwrap_or(' ');
+                (ch, cell.cell_index(), cell.width())
+            })
+            .collect();
+
+        let chars: Vec<char> = cells.iter().map(|(ch, ..)| *ch).collect();
+
+        let mut context = BidiContext::new();
+        context.resolve_paragraph(&chars, hint);
+
+        let columns = cells
+            .last()
+            .map(|(_, index, width)| index + width)
+            .unwrap_or_default();
+
+        let mut visual_columns: Vec<usize> = (0..columns).collect();
+        let mut visual_column = 0;
+        for run in context.reordered_runs(0..chars.len()) {
+            for cell in run.indices {
+                let (_, logical_column, width) = cells[cell];
+                // Detail: The columns of wide characters keep their order.
+                for i in 0..width {
+                    visual_columns[logical_column + i] = visual_column + i;
+                }
+                visual_column += width;
+            }
+        }
+
+        if visual_columns.iter().enumerate().all(|(l, v)| l == *v) {
+            return None;
+        }
+
+        let mut logical_columns = vec![0; columns];
+        for (logical, visual) in visual_columns.iter().enumerate() {
+            logical_columns[*visual] = logical;
+        }
+
+        Some(Self {
+            visual_columns,
+            logical_columns,
+        })
+    }
+
+    pub fn visual_column(&self, logical_column: usize) -> usize {
+        self.visual_columns
+            .get(logical_column)
+            .copied()
+            .unwrap_or(logical_column)
+    }
+
+    pub fn logical_column(&self, visual_column: usize) -> usize {
+        self.logical_columns
+            .get(visual_column)
+            .copied()
+            .unwrap_or(visual_column)
+    }
+
+    /// The leftmost visual column of a range of logical columns.
+    ///
+    /// Ranges of cells that don't cross direction boundaries are visually contiguous.
+    pub fn visual_start(&self, logical_columns: Range<usize>) -> usize {
+        logical_columns
+            .map(|c| self.visual_column(c))
+            .min()
+            .expect("Internal error: Empty range of columns")
+    }
+}
@@ -0,0 +1,130 @@
+//! Minimum contrast enforcement between foreground and background colors.
+//!
+//! Contrast ratios are computed as defined by WCAG 2. Foreground colors are adjusted in the Oklab
+//! color space, so that only their perceived lightness changes.
+//!
+//! Detail: Computations are done in `f64`, `f32` can not represent the Oklab matrices precisely.
+use termwiz::color::SrgbaTuple;
+
+/// The number of bisection steps used to find the lightness that satisfies the contrast ratio.
+const LIGHTNESS_SEARCH_STEPS: usize = 16;
+
+/// Adjust the lightness of the foreground color so that its contrast ratio to the background is at
+/// least `minimum_ratio`.
+///
+/// If the ratio can not be reached, the foreground color is set to the lightness with the highest
+/// contrast. Alpha is preserved.
+pub fn ensure_minimum_contrast(
+    foreground: SrgbaTuple,
+    background: SrgbaTuple,
+    minimum_ratio: f32,
+) -> SrgbaTuple {
+    let minimum_ratio = minimum_ratio as f64;
+    let background_luminance = relative_luminance(background);
+    if contrast_ratio(relative_luminance(foreground), background_luminance) >= minimum_ratio {
+        return foreground;
+    }
+
+    let SrgbaTuple(r, g, b, alpha) = foreground;
+    let [lightness, a, b] = linear_to_oklab([r, g, b].map(|c| to_linear(c as f64)));
+
+    let contrast_at = |lightness: f64| {
+        let rgb = oklab_to_linear([lightness, a, b]).map(|c| c.clamp(0., 1.));
+        contrast_ratio(linear_luminance(rgb), background_luminance)
+    };
+
+    // Search towards white and black and use the one that needs the smaller adjustment.
+    let lighter = lightness_reaching(lightness, 1., minimum_ratio, contrast_at);
+    let darker = lightness_reaching(lightness, 0., minimum_ratio, contrast_at);
+    let lightness = match (lighter, darker) {
+        (Some(lighter), Some(darker)) if lighter - lightness <= lightness - darker => lighter,
+        (Some(lighter), None) => lighter,
+        (_, Some(darker)) => darker,
+        (None, None) if contrast_at(1.) >= contrast_at(0.) => 1.,
+        (None, None) => 0.,
+    };
+
+    let [r, g, b] = oklab_to_linear([lightness, a, b]).map(|c| from_linear(c.clamp(0., 1.)) as f32);
+    SrgbaTuple(r, g, b, alpha)
+}
+
+/// Find the lightness closest to `from` in the direction of `to` that reaches the minimum ratio.
+fn lightness_reaching(
+    from: f64,
+    to: f64,
+    minimum_ratio: f64,
+    contrast_at: impl Fn(f64) -> f64,
+) -> Option<f64> {
+    if contrast_at(to) < minimum_ratio {
+        return None;
+    }
+
+    // Invariant: `satisfying` always reaches the ratio, `failing` does not.
+    let (mut failing, mut satisfying) = (from, to);
+    for _ in 0..LIGHTNESS_SEARCH_STEPS {
+        let mid = (failing + satisfying) / 2.;
+        if contrast_at(mid) >= minimum_ratio {
+            satisfying = mid;
+        } else {
+            failing = mid;
+        }
+    }
+    Some(satisfying)
+}
+
+fn contrast_ratio(luminance_a: f64, luminance_b: f64) -> f64 {
+    let (lighter, darker) = if luminance_a > luminance_b {
+        (luminance_a, luminance_b)
+    } else {
+        (luminance_b, luminance_a)
+    };
+    (lighter + 0.05) / (darker + 0.05)
+}
+
+fn relative_luminance(SrgbaTuple(r, g, b, _): SrgbaTuple) -> f64 {
+    linear_luminance([r, g, b].map(|c| to_linear(c as f64)))
+}
+
+fn linear_luminance([r, g, b]: [f64; 3]) -> f64 {
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+fn to_linear(c: f64) -> f64 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn from_linear(c: f64) -> f64 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1. / 2.4) - 0.055
+    }
+}
+
+fn linear_to_oklab([r, g, b]: [f64; 3]) -> [f64; 3] {
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+
+    [
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    ]
+}
+
+fn oklab_to_linear([lightness, a, b]: [f64; 3]) -> [f64; 3] {
+    let l = (lightness + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+    let m = (lightness - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+    let s = (lightness - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+
+    [
+        4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+        -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+        -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+    ]
+}
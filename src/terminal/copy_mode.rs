@@ -0,0 +1,314 @@
+//! Keyboard driven navigation and selection in the scrollback.
+//!
+//! Copy mode moves a virtual cursor with vi motions. Selections started from the keyboard are
+//! regular [`Selection`]s anchored at the cursor position they were started from.
+use std::ops::Range;
+
+use wezterm_term::{KeyCode, KeyModifiers, StableRowIndex, Terminal};
+
+use crate::terminal::{CellPos, ScreenGeometry, Selection, SelectionMode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyModeMotion {
+    Left,
+    Right,
+    Up,
+    Down,
+    /// `w`
+    NextWordStart,
+    /// `b`
+    PreviousWordStart,
+    /// `e`
+    WordEnd,
+    /// `0`
+    LineStart,
+    /// `$`
+    LineEnd,
+    /// `gg`
+    Top,
+    /// `G`
+    Bottom,
+    /// `Ctrl-u`
+    HalfPageUp,
+    /// `Ctrl-d`
+    HalfPageDown,
+}
+
+#[derive(Debug)]
+pub struct CopyMode {
+    cursor: CellPos,
+    /// The mode and the anchor of the selection, `None` if not selecting.
+    selecting: Option<(SelectionMode, CellPos)>,
+    /// Set after the first `g` of `gg`.
+    pending_g: bool,
+}
+
+impl CopyMode {
+    pub fn new(cursor: CellPos) -> Self {
+        Self {
+            cursor,
+            selecting: None,
+            pending_g: false,
+        }
+    }
+
+    pub fn cursor(&self) -> CellPos {
+        self.cursor
+    }
+
+    pub fn set_cursor(&mut self, cursor: CellPos) {
+        self.cursor = cursor;
+    }
+
+    /// Whether the modifiers leave a key to its copy mode meaning.
+    ///
+    /// Detail: Shifted characters like `V`, `$`, or `G` are reported with the shift modifier.
+    pub fn is_unmodified(modifiers: KeyModifiers) -> bool {
+        (modifiers - KeyModifiers::SHIFT).is_empty()
+    }
+
+    /// The motion of a key, `None` if the key does not move the cursor.
+    pub fn key_motion(&mut self, key: KeyCode, modifiers: KeyModifiers) -> Option<CopyModeMotion> {
+        let pending_g = std::mem::take(&mut self.pending_g);
+
+        match key {
+            KeyCode::Char('u') if modifiers == KeyModifiers::CTRL => {
+                Some(CopyModeMotion::HalfPageUp)
+            }
+            KeyCode::Char('d') if modifiers == KeyModifiers::CTRL => {
+                Some(CopyModeMotion::HalfPageDown)
+            }
+            _ if !Self::is_unmodified(modifiers) => None,
+            KeyCode::Char('h') | KeyCode::LeftArrow => Some(CopyModeMotion::Left),
+            KeyCode::Char('j') | KeyCode::DownArrow => Some(CopyModeMotion::Down),
+            KeyCode::Char('k') | KeyCode::UpArrow => Some(CopyModeMotion::Up),
+            KeyCode::Char('l') | KeyCode::RightArrow => Some(CopyModeMotion::Right),
+            KeyCode::Char('w') => Some(CopyModeMotion::NextWordStart),
+            KeyCode::Char('b') => Some(CopyModeMotion::PreviousWordStart),
+            KeyCode::Char('e') => Some(CopyModeMotion::WordEnd),
+            KeyCode::Char('0') | KeyCode::Home => Some(CopyModeMotion::LineStart),
+            KeyCode::Char('$') | KeyCode::End => Some(CopyModeMotion::LineEnd),
+            KeyCode::Char('g') if pending_g => Some(CopyModeMotion::Top),
+            KeyCode::Char('g') => {
+                self.pending_g = true;
+                None
+            }
+            KeyCode::Char('G') => Some(CopyModeMotion::Bottom),
+            _ => None,
+        }
+    }
+
+    pub fn is_selecting(&self) -> bool {
+        self.selecting.is_some()
+    }
+
+    /// Start selecting in the mode, switch to it, or stop selecting if it's the current mode.
+    pub fn toggle_selection(&mut self, mode: SelectionMode) {
+        self.selecting = match self.selecting {
+            Some((current, _)) if current == mode => None,
+            Some((_, anchor)) => Some((mode, anchor)),
+            None => Some((mode, self.cursor)),
+        }
+    }
+
+    pub fn stop_selecting(&mut self) {
+        self.selecting = None;
+    }
+
+    /// The selection from the anchor to the cursor.
+    pub fn selection(&self) -> Selection {
+        match self.selecting {
+            Some((mode, anchor)) => Selection::Selected {
+                mode,
+                from: anchor,
+                to: self.cursor,
+                resolved: None,
+            },
+            None => Selection::Unselected,
+        }
+    }
+
+    /// Move the cursor. `rows` is the number of rows of the terminal.
+    pub fn move_cursor(&mut self, motion: CopyModeMotion, terminal: &Terminal, rows: usize) {
+        let screen_geometry = ScreenGeometry::new(terminal.screen());
+        let buffer_area = screen_geometry.buffer_area;
+        let columns = screen_geometry.columns;
+        let rows = rows as StableRowIndex;
+
+        let CellPos { column, row } = self.clamped_cursor(&buffer_area, columns);
+
+        self.cursor = match motion {
+            CopyModeMotion::Left => CellPos::new((column - 1).max(0), row),
+            CopyModeMotion::Right => CellPos::new((column + 1).min(columns.cast_signed() - 1), row),
+            CopyModeMotion::Up => CellPos::new(column, (row - 1).max(buffer_area.start)),
+            CopyModeMotion::Down => CellPos::new(column, (row + 1).min(buffer_area.end - 1)),
+            CopyModeMotion::NextWordStart | CopyModeMotion::WordEnd => {
+                let cells = Cells::new(terminal, row..(row + rows).min(buffer_area.end), columns);
+                let index = cells.index(CellPos::new(column, row));
+                cells.pos(if motion == CopyModeMotion::WordEnd {
+                    cells.word_end(index)
+                } else {
+                    cells.next_word_start(index)
+                })
+            }
+            CopyModeMotion::PreviousWordStart => {
+                let cells = Cells::new(
+                    terminal,
+                    (row - rows + 1).max(buffer_area.start)..row + 1,
+                    columns,
+                );
+                let index = cells.index(CellPos::new(column, row));
+                cells.pos(cells.previous_word_start(index))
+            }
+            CopyModeMotion::LineStart => CellPos::new(0, row),
+            CopyModeMotion::LineEnd => {
+                let cells = Cells::new(terminal, row..row + 1, columns);
+                let last = cells
+                    .classes
+                    .iter()
+                    .rposition(|c| *c != CellClass::Blank)
+                    .unwrap_or_default();
+                CellPos::new(last.cast_signed(), row)
+            }
+            CopyModeMotion::Top => CellPos::new(0, buffer_area.start),
+            CopyModeMotion::Bottom => CellPos::new(0, buffer_area.end - 1),
+            CopyModeMotion::HalfPageUp => {
+                CellPos::new(column, (row - rows / 2).max(buffer_area.start))
+            }
+            CopyModeMotion::HalfPageDown => {
+                CellPos::new(column, (row + rows / 2).min(buffer_area.end - 1))
+            }
+        };
+    }
+
+    /// The cursor clamped to the buffer, which may have changed since the cursor was placed.
+    fn clamped_cursor(&self, buffer_area: &Range<StableRowIndex>, columns: usize) -> CellPos {
+        CellPos::new(
+            self.cursor.column.clamp(0, columns.cast_signed() - 1),
+            self.cursor
+                .row
+                .clamp(buffer_area.start, buffer_area.end - 1),
+        )
+    }
+}
+
+/// Classes of cells used to find word boundaries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CellClass {
+    Blank,
+    /// Word characters of the logical line with the index.
+    Word(usize),
+    /// Other characters of the logical line with the index.
+    Punctuation(usize),
+}
+
+impl CellClass {
+    fn new(s: &str, logical_line: usize) -> Self {
+        match s.chars().next() {
+            None => Self::Blank,
+            Some(c) if c.is_whitespace() => Self::Blank,
+            Some(c) if c.is_alphanumeric() || c == '_' => Self::Word(logical_line),
+            Some(_) => Self::Punctuation(logical_line),
+        }
+    }
+}
+
+/// The classes of the cells of a range of rows, row by row.
+///
+/// Detail: Words never continue across rows that are not wrapped, because every logical line gets
+/// its own classes.
+#[derive(Debug)]
+struct Cells {
+    first_row: StableRowIndex,
+    columns: usize,
+    classes: Vec<CellClass>,
+}
+
+impl Cells {
+    /// `rows` must be inside the terminal's buffer area.
+    fn new(terminal: &Terminal, rows: Range<StableRowIndex>, columns: usize) -> Self {
+        let screen = terminal.screen();
+        let mut classes = Vec::with_capacity(rows.len() * columns);
+        let mut logical_line = 0;
+
+        screen.with_phys_lines(screen.stable_range(&rows), |lines| {
+            for line in lines {
+                let start = classes.len();
+                classes.resize(start + columns, CellClass::Blank);
+                for cell in line.visible_cells() {
+                    let class = CellClass::new(cell.str(), logical_line);
+                    let cell_columns = cell.cell_index()..(cell.cell_index() + cell.width());
+                    for column in cell_columns.take_while(|c| *c < columns) {
+                        classes[start + column] = class;
+                    }
+                }
+                if !line.last_cell_was_wrapped() {
+                    logical_line += 1;
+                }
+            }
+        });
+
+        Self {
+            first_row: rows.start,
+            columns,
+            classes,
+        }
+    }
+
+    fn index(&self, pos: CellPos) -> usize {
+        (pos.row - self.first_row).cast_unsigned() * self.columns + pos.column.cast_unsigned()
+    }
+
+    fn pos(&self, index: usize) -> CellPos {
+        CellPos::new(
+            (index % self.columns).cast_signed(),
+            self.first_row + (index / self.columns).cast_signed(),
+        )
+    }
+
+    fn next_word_start(&self, mut index: usize) -> usize {
+        let classes = &self.classes;
+        let class = classes[index];
+        if class != CellClass::Blank {
+            while index < classes.len() && classes[index] == class {
+                index += 1;
+            }
+        }
+        while index < classes.len() && classes[index] == CellClass::Blank {
+            index += 1;
+        }
+        index.min(classes.len() - 1)
+    }
+
+    fn word_end(&self, mut index: usize) -> usize {
+        let classes = &self.classes;
+        index += 1;
+        while index < classes.len() && classes[index] == CellClass::Blank {
+            index += 1;
+        }
+        if index >= classes.len() {
+            return classes.len() - 1;
+        }
+        let class = classes[index];
+        while index + 1 < classes.len() && classes[index + 1] == class {
+            index += 1;
+        }
+        index
+    }
+
+    fn previous_word_start(&self, mut index: usize) -> usize {
+        let classes = &self.classes;
+        if index == 0 {
+            return 0;
+        }
+        index -= 1;
+        while index > 0 && classes[index] == CellClass::Blank {
+            index -= 1;
+        }
+        let class = classes[index];
+        while index > 0 && classes[index - 1] == class {
+            index -= 1;
+        }
+        index
+    }
+}
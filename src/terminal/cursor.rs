@@ -1,15 +1,24 @@
 // Cursor
 
+use massive_geometry::{Point, Rect, Size};
 use termwiz::surface::CursorVisibility;
+use wezterm_bidi::ParagraphDirectionHint;
 use wezterm_term::{CursorPosition, StableRowIndex, Terminal};
 
-use crate::{terminal::ScreenGeometry, view_state::ViewState};
+use crate::{
+    terminal::{LineSize, ScreenGeometry, TerminalViewGeometry, VisualOrder},
+    view_state::ViewState,
+};
 
 #[derive(Debug, Clone)]
 pub struct CursorMetrics {
     pub pos: CursorPosition,
     pub stable_y: StableRowIndex,
+    /// The column the cursor is rendered at. Differs from `pos.x` if the line is not rendered in
+    /// logical order.
+    pub visual_x: usize,
     pub width: usize,
+    pub line_size: LineSize,
     pub focused: bool,
 }
 
@@ -18,6 +27,7 @@ impl CursorMetrics {
         terminal: &mut Terminal,
         screen_geometry: &ScreenGeometry,
         window_state: &ViewState,
+        bidi_hint: Option<ParagraphDirectionHint>,
     ) -> Option<Self> {
         let pos = terminal.cursor_pos();
         if pos.visibility == CursorVisibility::Hidden {
@@ -28,18 +38,34 @@ impl CursorMetrics {
 
         let stable_y = screen_geometry.default_input_area.start + pos.y as StableRowIndex;
         let phys_y = screen.phys_row(pos.y);
+        let line = &*screen.line_mut(phys_y);
         // Detail: This uses `visible_cells()`.
-        let width = screen
-            .line_mut(phys_y)
-            .get_cell(pos.x)
-            .map(|c| c.width())
-            .unwrap_or(1);
+        let width = line.get_cell(pos.x).map(|c| c.width()).unwrap_or(1);
+        let line_size = LineSize::from_line(line);
+        let visual_x = bidi_hint
+            .and_then(|hint| VisualOrder::from_line(line, hint))
+            .map_or(pos.x, |order| order.visual_column(pos.x));
 
         Some(Self {
             pos,
             stable_y,
+            visual_x,
             width,
+            line_size,
             focused: window_state.focused,
         })
     }
+
+    /// The area the cursor covers in view pixels.
+    pub fn rect_px(&self, view_geometry: &TerminalViewGeometry) -> Rect {
+        let cell_size_px = view_geometry.terminal.cell_size_px;
+        let column_width = (cell_size_px.width as usize * self.line_size.width_scale()) as f64;
+        let top = (self.stable_y - view_geometry.stable_range.start) as f64
+            * view_geometry.line_height_px() as f64
+            - view_geometry.stable_range_ascend_px as f64;
+        Rect::new(
+            Point::new(self.visual_x as f64 * column_width, top),
+            Size::new(self.width as f64 * column_width, cell_size_px.height as f64),
+        )
+    }
 }
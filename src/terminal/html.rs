@@ -0,0 +1,74 @@
+//! Writing styled terminal text as HTML.
+//!
+//! Feature: RTF. `arboard` only supports plain text, HTML, and images.
+use std::fmt::Write;
+
+use termwiz::color::SrgbaTuple;
+
+/// The CSS style of a run of cells.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CellStyle {
+    pub foreground: String,
+    /// `None` if the cells are rendered on the default background.
+    pub background: Option<String>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikethrough: bool,
+}
+
+impl CellStyle {
+    fn css(&self) -> String {
+        let mut css = format!("color:{}", self.foreground);
+        if let Some(background) = &self.background {
+            write!(css, ";background-color:{background}").unwrap();
+        }
+        if self.bold {
+            css.push_str(";font-weight:bold");
+        }
+        if self.italic {
+            css.push_str(";font-style:italic");
+        }
+        match (self.underline, self.strikethrough) {
+            (true, true) => css.push_str(";text-decoration:underline line-through"),
+            (true, false) => css.push_str(";text-decoration:underline"),
+            (false, true) => css.push_str(";text-decoration:line-through"),
+            (false, false) => {}
+        }
+        css
+    }
+}
+
+/// The CSS color of a resolved color.
+///
+/// Precision: Alpha is ignored.
+pub fn css_color(color: SrgbaTuple) -> String {
+    color.to_rgb_string()
+}
+
+pub fn push_span(html: &mut String, style: &CellStyle, text: &str) {
+    write!(html, "<span style=\"{}\">", style.css()).unwrap();
+    push_escaped(html, text);
+    html.push_str("</span>");
+}
+
+pub fn push_escaped(html: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '&' => html.push_str("&amp;"),
+            '"' => html.push_str("&quot;"),
+            c => html.push(c),
+        }
+    }
+}
+
+/// Wrap the spans in a preformatted block with the default colors.
+pub fn document(foreground: SrgbaTuple, background: SrgbaTuple, body: &str) -> String {
+    format!(
+        "<pre style=\"font-family:monospace;color:{};background-color:{}\">{body}</pre>",
+        css_color(foreground),
+        css_color(background)
+    )
+}
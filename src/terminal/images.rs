@@ -0,0 +1,177 @@
+//! Rendering of image cells.
+//!
+//! `wezterm_term` decodes sixel, iTerm2 inline images (OSC 1337) and the kitty graphics protocol
+//! into [`ImageCell`]s that are attached to the cells they cover. Every cell only shows a fragment
+//! of the image, described by its texture coordinates.
+use std::{num::NonZeroUsize, sync::Arc};
+
+use log::warn;
+use lru::LruCache;
+use termwiz::image::{ImageCell, ImageDataType};
+use wezterm_term::Line;
+
+use massive_geometry::{Rect, SizePx};
+use massive_shapes::{Image, ImageData, Shape};
+
+use crate::terminal::{LineSize, VisualOrder};
+
+/// The number of decoded images we keep around.
+///
+/// Performance: This should probably be based on the memory the images take up.
+const MAX_DECODED_IMAGES: usize = 64;
+
+/// Image shapes of a line, separated by their z-order relative to the text.
+#[derive(Debug, Default)]
+pub struct LineImages {
+    pub below_text: Vec<Shape>,
+    pub above_text: Vec<Shape>,
+}
+
+/// Caches the decoded image data, so that the images don't need to be decoded every time a line
+/// changes.
+#[derive(Debug)]
+pub struct ImageCache {
+    /// `None` if decoding failed, so that we don't retry and warn on every line update.
+    decoded: LruCache<[u8; 32], Option<Arc<ImageData>>>,
+}
+
+impl Default for ImageCache {
+    fn default() -> Self {
+        Self {
+            decoded: LruCache::new(NonZeroUsize::new(MAX_DECODED_IMAGES).unwrap()),
+        }
+    }
+}
+
+impl ImageCache {
+    /// Create the shapes for all the image fragments of a line.
+    ///
+    /// Like the glyphs, the fragments are placed at the visual columns of their cells. On double
+    /// width lines, they are stretched to the width of the cells.
+    pub fn line_images(
+        &mut self,
+        line: &Line,
+        visual_order: Option<&VisualOrder>,
+        cell_size_px: SizePx,
+        top: i64,
+    ) -> LineImages {
+        let mut images = LineImages::default();
+        let width_scale = LineSize::from_line(line).width_scale() as u32;
+        let cell_size_px: SizePx = (cell_size_px.width * width_scale, cell_size_px.height).into();
+
+        for cell in line.visible_cells() {
+            let Some(image_cells) = cell.attrs().images() else {
+                continue;
+            };
+
+            let cell_index = cell.cell_index();
+            let visual_column = visual_order.map_or(cell_index, |o| o.visual_column(cell_index));
+            let left = visual_column as i64 * cell_size_px.width as i64;
+
+            for image_cell in image_cells {
+                let Some(shape) = self.image_cell_shape(&image_cell, cell_size_px, (left, top))
+                else {
+                    continue;
+                };
+
+                // Precision: Kitty places images with a z-index below `i32::MIN / 2` even below
+                // the cell backgrounds. We render them like all other negative z-indices.
+                if image_cell.z_index() < 0 {
+                    images.below_text.push(shape);
+                } else {
+                    images.above_text.push(shape);
+                }
+            }
+        }
+
+        images
+    }
+
+    /// Create the shape for the image fragment shown in one cell.
+    ///
+    /// The fragment is clipped to the cell, reduced by the image cell's padding.
+    fn image_cell_shape(
+        &mut self,
+        image_cell: &ImageCell,
+        cell_size_px: SizePx,
+        (left, top): (i64, i64),
+    ) -> Option<Shape> {
+        let image_data = image_cell.image_data();
+        let data = self
+            .decoded
+            .get_or_insert(image_data.hash(), || decode(image_data))
+            .clone()?;
+
+        let (padding_left, padding_top, padding_right, padding_bottom) = (
+            image_cell.padding_left() as f64,
+            image_cell.padding_top() as f64,
+            image_cell.padding_right() as f64,
+            image_cell.padding_bottom() as f64,
+        );
+
+        let width = cell_size_px.width as f64 - padding_left - padding_right;
+        let height = cell_size_px.height as f64 - padding_top - padding_bottom;
+        if width <= 0. || height <= 0. {
+            return None;
+        }
+
+        let rect = Rect::new(
+            (left as f64 + padding_left, top as f64 + padding_top),
+            (width, height),
+        );
+
+        // Texture coordinates are normalized to 0..1.
+        let (top_left, bottom_right) = (image_cell.top_left(), image_cell.bottom_right());
+        let texture_rect = Rect::new(
+            (
+                top_left.x.into_inner() as f64,
+                top_left.y.into_inner() as f64,
+            ),
+            (
+                (bottom_right.x - top_left.x).into_inner() as f64,
+                (bottom_right.y - top_left.y).into_inner() as f64,
+            ),
+        );
+
+        Some(
+            Image::new(rect, data)
+                .with_texture_rect(texture_rect)
+                .into(),
+        )
+    }
+}
+
+fn decode(image_data: &termwiz::image::ImageData) -> Option<Arc<ImageData>> {
+    // Performance: Decoding happens while the font system is not locked, but still on the
+    // presentation thread.
+    let data = image_data.data();
+    let decoded = match &*data {
+        ImageDataType::EncodedFile(_) | ImageDataType::EncodedLease(_) => data.clone().decode(),
+        data => data.clone(),
+    };
+
+    match decoded {
+        ImageDataType::Rgba8 {
+            data,
+            width,
+            height,
+            ..
+        } => Some(ImageData::rgba8((width, height), data).into()),
+        // Feature: Animate images. For now we show the first frame only.
+        ImageDataType::AnimRgba8 {
+            mut frames,
+            width,
+            height,
+            ..
+        } if !frames.is_empty() => {
+            Some(ImageData::rgba8((width, height), frames.swap_remove(0)).into())
+        }
+        _ => {
+            warn!(
+                "Failed to decode image data with hash {:x?}",
+                image_data.hash()
+            );
+            None
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use termwiz::surface::SequenceNo;
+use wezterm_term::Line;
+
+/// The size at which a line is rendered (DECDWL / DECDHL).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineSize {
+    #[default]
+    Single,
+    DoubleWidth,
+    /// The top half of a double height line. Also double width.
+    DoubleHeightTop,
+    /// The bottom half of a double height line. Also double width.
+    DoubleHeightBottom,
+}
+
+impl LineSize {
+    pub fn from_line(line: &Line) -> Self {
+        if line.is_double_height_top() {
+            Self::DoubleHeightTop
+        } else if line.is_double_height_bottom() {
+            Self::DoubleHeightBottom
+        } else if line.is_double_width() {
+            Self::DoubleWidth
+        } else {
+            Self::Single
+        }
+    }
+
+    /// Render a line at this size.
+    pub fn apply_to(self, line: &mut Line, seqno: SequenceNo) {
+        match self {
+            Self::Single => line.set_single_width(seqno),
+            Self::DoubleWidth => line.set_double_width(seqno),
+            Self::DoubleHeightTop => line.set_double_height_top(seqno),
+            Self::DoubleHeightBottom => line.set_double_height_bottom(seqno),
+        }
+    }
+
+    /// The number of cells a column of this line covers horizontally.
+    pub fn width_scale(self) -> usize {
+        match self {
+            Self::Single => 1,
+            Self::DoubleWidth | Self::DoubleHeightTop | Self::DoubleHeightBottom => 2,
+        }
+    }
+
+    pub fn is_double_height(self) -> bool {
+        matches!(self, Self::DoubleHeightTop | Self::DoubleHeightBottom)
+    }
+}
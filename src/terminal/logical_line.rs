@@ -100,3 +100,49 @@ fn logical_from_physicals(physical_lines: &[&Line]) -> Line {
 
     logical_line
 }
+
+/// The text of a line and the cells its bytes belong to.
+///
+/// Used to map matches in the text back to cells.
+#[derive(Debug)]
+pub struct LineText {
+    pub text: String,
+    /// The byte offset into `text` at which each cell starts, its index and width.
+    cells: Vec<(usize, usize, usize)>,
+}
+
+impl LineText {
+    pub fn new(line: &Line) -> Self {
+        let mut text = String::new();
+        let mut cells = Vec::new();
+        for cell in line.visible_cells() {
+            cells.push((text.len(), cell.cell_index(), cell.width()));
+            text.push_str(cell.str());
+        }
+        Self { text, cells }
+    }
+
+    /// The byte offset of the text of the cell that covers the cell index.
+    pub fn byte_offset(&self, cell_index: usize) -> Option<usize> {
+        self.cells
+            .iter()
+            .find(|(_, index, width)| (*index..index + width).contains(&cell_index))
+            .map(|(byte, ..)| *byte)
+    }
+
+    /// The half-open range of cell indices covering a byte range of the text.
+    ///
+    /// Returns `None` if the byte range is empty.
+    pub fn cell_range(&self, bytes: Range<usize>) -> Option<Range<usize>> {
+        if bytes.is_empty() {
+            return None;
+        }
+        let cell_at = |byte: usize| {
+            let i = self.cells.partition_point(|(start, ..)| *start <= byte);
+            self.cells[i - 1]
+        };
+        let (_, first, _) = cell_at(bytes.start);
+        let (_, last, last_width) = cell_at(bytes.end - 1);
+        Some(first..last + last_width)
+    }
+}
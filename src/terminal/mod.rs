@@ -1,21 +1,34 @@
+mod bidi;
+mod contrast;
+mod copy_mode;
 mod cursor;
 mod font;
 mod geometry;
+mod html;
+mod images;
+mod line_size;
 mod logical_line;
 mod presenter;
 mod screen_geometry;
 mod scroll_locations;
+mod scrollbar;
 mod scroller;
+mod search;
 mod selection;
 mod view;
 mod view_geometry;
 
+pub use bidi::*;
+pub use copy_mode::*;
 pub use font::*;
 pub use geometry::*;
+pub use line_size::*;
 pub use logical_line::*;
 pub use presenter::*;
 pub use screen_geometry::*;
+pub use scrollbar::*;
 pub use scroller::*;
+pub use search::*;
 pub use selection::*;
 pub use view::*;
 pub use view_geometry::*;
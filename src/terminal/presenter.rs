@@ -1,14 +1,14 @@
-use std::{ops::Range, sync::Arc};
+use std::{ops::Range, sync::Arc, time::Instant};
 
 use anyhow::Result;
 use derive_more::Debug;
 use log::{info, trace, warn};
-use massive_geometry::SizePx;
+use massive_geometry::{Rect, SizePx};
 use parking_lot::Mutex;
 
 use rangeset::RangeSet;
-use termwiz::surface::SequenceNo;
-use wezterm_term::{Hyperlink, Line, Screen, StableRowIndex, Terminal};
+use termwiz::{cell::unicode_column_width, surface::SequenceNo};
+use wezterm_term::{Hyperlink, Line, Screen, SemanticType, StableRowIndex, Terminal};
 
 use massive_animation::TimeScale;
 use massive_shell::Scene;
@@ -18,8 +18,10 @@ use crate::{
     TerminalView, ViewState,
     range_ops::{RangeOps, WithLength},
     terminal::{
-        ScreenGeometry, SelectedRange, Selection, SelectionMode, TerminalGeometry,
-        TerminalViewGeometry, TerminalViewParams, cursor::CursorMetrics,
+        CellPos, CopyMode, CopyModeMotion, ScreenGeometry, ScrollbarGeometry, ScrollbarHit,
+        ScrollbarTicks, Search, SelectedRange, Selection, SelectionMode, TerminalFont,
+        TerminalGeometry, TerminalScroller, TerminalViewGeometry, TerminalViewParams,
+        cursor::CursorMetrics, smart_selection_around, zone_around,
     },
     view_geometry::PixelPoint,
 };
@@ -40,11 +42,38 @@ pub struct TerminalPresenter {
 
     selection: Selection,
 
+    /// The active search, `None` if the user is not searching.
+    search: Option<Search>,
+
+    /// The keyboard copy mode, `None` if it's not active.
+    copy_mode: Option<CopyMode>,
+
+    /// The text the input method is composing.
+    preedit: Option<String>,
+    /// The area of the cursor behind the composed text, `None` if the cursor is hidden.
+    ime_cursor_area: Option<Rect>,
+
+    /// The scrollbar as it was last rendered, `None` if there is nothing to scroll.
+    scrollbar: Option<ScrollbarGeometry>,
+    /// The distance from the top of the scrollbar's thumb to where it was grabbed, `None` if it's
+    /// not being dragged.
+    scrollbar_drag: Option<f64>,
+    /// Set if the left button press hit the scrollbar, so that its release is consumed, too.
+    scrollbar_pressed: bool,
+    /// The terminal, the search matches, or the scrollbar changed since the ticks on the
+    /// scrollbar were built.
+    scrollbar_ticks_outdated: bool,
+
     /// The currently underlined hyperlink, updated in update based on `mouse_pointer`.
     ///
     /// This needs to be stored to update the lines that cover it when its highlighting state
     /// changes.
     underlined_hyperlink: Option<HighlightedHyperlink>,
+    /// The selected range the lines were last rendered with.
+    ///
+    /// Selected cells are rendered with the selection foreground color, so the lines covered by
+    /// the previous and the current selection need to be updated when it changes.
+    rendered_selection: Option<(SelectedRange, SelectionMode)>,
     pub last_rendered_seq_no: SequenceNo,
     temporary_line_buf: Vec<Line>,
 
@@ -66,8 +95,18 @@ impl TerminalPresenter {
 
             scroll_state: Default::default(),
             selection: Default::default(),
+            search: None,
+            copy_mode: None,
+            preedit: None,
+            ime_cursor_area: None,
+
+            scrollbar: None,
+            scrollbar_drag: None,
+            scrollbar_pressed: false,
+            scrollbar_ticks_outdated: true,
 
             underlined_hyperlink: None,
+            rendered_selection: None,
             last_rendered_seq_no,
             temporary_line_buf: Vec::new(),
 
@@ -103,11 +142,139 @@ impl TerminalPresenter {
         Ok(true)
     }
 
+    /// Change the font and fit the terminal into its current pixel size.
+    ///
+    /// Returns `true` if the terminal size in cells changed.
+    ///
+    /// Precision: The pixels left over by the previous cell size are lost.
+    pub fn set_font(&mut self, font: TerminalFont, scene: &Scene) -> Result<bool> {
+        let size_px = self.geometry.size_px();
+        let scroll_offset = self.terminal.lock().screen().visible_row_to_stable_row(0);
+
+        // The line at the top of the view, including the fraction it is scrolled out.
+        let top_line = match self.scroll_state {
+            ScrollState::Auto => None,
+            _ => Some(self.view.final_scroll_offset_px() / self.geometry.line_height_px() as f64),
+        };
+
+        let mut params = self.view.params.clone();
+        params.font = font;
+        self.geometry.cell_size_px = params.font.cell_size_px();
+        // The new view renders all lines with the new font.
+        self.view = TerminalView::new(params, self.view.alt_screen, scene, scroll_offset);
+        self.rendered_selection = None;
+        self.scroll_state = match top_line {
+            None => ScrollState::Auto,
+            Some(line) => ScrollState::RestingPixel(line * self.geometry.line_height_px() as f64),
+        };
+
+        self.resize(size_px)
+    }
+
+    /// The area the input method should place its candidate window next to.
+    pub fn ime_cursor_area(&self) -> Option<Rect> {
+        self.ime_cursor_area
+    }
+
+    /// Set the text the input method is composing, `None` if it's not composing.
+    pub fn set_preedit(&mut self, preedit: Option<String>) {
+        self.preedit = preedit;
+    }
+
     pub fn scroll_delta_px(&mut self, delta: f64) {
         let current = self.view.final_scroll_offset_px();
         self.scroll_state = ScrollState::RestingPixel(current + delta);
     }
 
+    /// Scroll the row to the top of the view.
+    pub fn scroll_to_row(&mut self, row: StableRowIndex) {
+        self.scroll_state = ScrollState::RestingPixel(self.geometry.stable_px_offset(row) as f64);
+    }
+
+    pub fn scroll_to_top(&mut self) {
+        let buffer_area = ScreenGeometry::new(self.terminal.lock().screen()).buffer_area;
+        self.scroll_to_row(buffer_area.start);
+    }
+
+    /// Scroll to the bottom and follow the output from there on.
+    pub fn scroll_to_bottom(&mut self) {
+        self.enable_autoscroll();
+    }
+
+    /// Scroll the first prompt above the top of the view to the top.
+    pub fn scroll_to_previous_prompt(&mut self) -> Result<()> {
+        let top = self.scroll_target_row();
+        let prompt = Self::query_prompt_rows(&mut self.terminal.lock())?
+            .into_iter()
+            .rfind(|row| *row < top);
+        if let Some(row) = prompt {
+            self.scroll_to_row(row);
+        }
+        Ok(())
+    }
+
+    /// Scroll the first prompt below the top of the view to the top.
+    pub fn scroll_to_next_prompt(&mut self) -> Result<()> {
+        let top = self.scroll_target_row();
+        let prompt = Self::query_prompt_rows(&mut self.terminal.lock())?
+            .into_iter()
+            .find(|row| *row > top);
+        if let Some(row) = prompt {
+            self.scroll_to_row(row);
+        }
+        Ok(())
+    }
+
+    /// The rows prompts start in, from top to bottom.
+    fn query_prompt_rows(terminal: &mut Terminal) -> Result<Vec<StableRowIndex>> {
+        Ok(terminal
+            .get_semantic_zones()?
+            .into_iter()
+            .filter(|zone| zone.semantic_type == SemanticType::Prompt)
+            .map(|zone| zone.start_y)
+            .collect())
+    }
+
+    /// The row at the top of the view when the scroll animation ends.
+    ///
+    /// Detail: Repeated jumps continue from where the previous one ends.
+    fn scroll_target_row(&self) -> StableRowIndex {
+        (self.view.final_scroll_offset_px() / self.geometry.line_height_px() as f64).floor()
+            as StableRowIndex
+    }
+
+    /// Scroll by a delta of a trackpad gesture. The scrolling continues after the gesture ended.
+    pub fn scroll_track_px(&mut self, scene: &Scene, delta: f64, now: Instant) {
+        match &mut self.scroll_state {
+            ScrollState::Kinetic(kinetic) => kinetic.scroller.track(delta, now),
+            state => {
+                let mut scroller = TerminalScroller::new(
+                    self.view.final_scroll_offset_px(),
+                    self.geometry.line_height_px() as f64,
+                );
+                scroller.track(delta, now);
+                *state = ScrollState::Kinetic(KineticScroller {
+                    scroller,
+                    time_scale: scene.time_scale(),
+                });
+            }
+        }
+    }
+
+    /// A trackpad gesture begins, which may be the momentum the platform supplies.
+    pub fn scroll_begin(&mut self, now: Instant) {
+        if let ScrollState::Kinetic(kinetic) = &mut self.scroll_state {
+            kinetic.scroller.begin(now);
+        }
+    }
+
+    /// The trackpad gesture ended.
+    pub fn scroll_release(&mut self, now: Instant) {
+        if let ScrollState::Kinetic(kinetic) = &mut self.scroll_state {
+            kinetic.scroller.release(now);
+        }
+    }
+
     pub fn apply_animations(&mut self) {
         self.view.apply_animations();
     }
@@ -135,7 +302,13 @@ impl TerminalPresenter {
 
         if Self::sync_alt_screen(&terminal, &mut self.view, scene) {
             self.selection = Selection::Unselected;
+            self.rendered_selection = None;
             self.scroll_state = ScrollState::Auto;
+            if let Some(search) = &mut self.search {
+                search.restart();
+            }
+            self.copy_mode = None;
+            self.scrollbar_ticks_outdated = true;
         }
 
         // Performance: May be there is need to lock the terminal if there are no visible changes
@@ -145,6 +318,8 @@ impl TerminalPresenter {
 
         let screen_geometry = ScreenGeometry::new(terminal.screen());
 
+        let config = self.view.params.config.clone();
+        let bidi_hint = config.bidi_hint();
         let view = &mut self.view;
 
         let view_geometry = view.geometry(&self.geometry);
@@ -241,6 +416,64 @@ impl TerminalPresenter {
             })
         }
 
+        // Compute the selection before the lines are retrieved, because the selected cells are
+        // rendered with the selection's foreground color.
+        let mut selected_range = view_geometry.selected_user_range(&self.selection).map(|r| {
+            r.extend(
+                self.selection.mode().unwrap(),
+                self.selection.resolved(),
+                &terminal,
+                &config,
+            )
+        });
+
+        {
+            let selection_rows = selected_range.map(|s| s.stable_rows()).unwrap_or_default();
+            let changes_intersect_with_selection =
+                changed_lines.iter().any(|l| selection_rows.contains(l));
+
+            // Clear the selection if changes intersect it and the user does not interact with it.
+            if changes_intersect_with_selection && !self.selection.can_progress() {
+                self.selection.reset();
+                selected_range = None;
+            }
+        }
+
+        let rectangular_selection = self.selection.mode() == Some(SelectionMode::Rectangular);
+
+        let rendered_selection = selected_range.zip(self.selection.mode());
+        if rendered_selection != self.rendered_selection {
+            // Performance: Only the rows with changed selected columns need to be updated.
+            let mut selection_changed_lines = RangeSet::new();
+            for (range, _) in [self.rendered_selection, rendered_selection]
+                .iter()
+                .flatten()
+            {
+                selection_changed_lines.add_range(range.stable_rows());
+            }
+            lines_requested.add_set(
+                &selection_changed_lines.intersection_with_range(view_visible_range.clone()),
+            );
+            self.rendered_selection = rendered_selection;
+        }
+
+        if let Some(search) = &mut self.search {
+            // Detail: Output changes the rows of the screen, which may not be visible in the view.
+            if terminal_updated
+                && let Some(screen_rows) = screen_geometry
+                    .buffer_area
+                    .intersect(&screen_geometry.default_input_area)
+            {
+                let mut changed_rows = RangeSet::new();
+                for row in screen.get_changed_stable_rows(screen_rows, self.last_rendered_seq_no) {
+                    changed_rows.add(row);
+                }
+                search.invalidate(&terminal, &changed_rows);
+            }
+            search.scan(&terminal, screen_geometry.buffer_area.clone());
+            self.scrollbar_ticks_outdated |= search.take_matches_changed();
+        }
+
         // Now the updated lines are known, but some of them might not be inside the terminal's
         // buffer range. Split them between terminal lines and empty ones.
         //
@@ -275,11 +508,8 @@ impl TerminalPresenter {
         // Gather everything from the terminal we need later.
 
         // Performance: Don't compute metrics if cursor hasn't changed.
-        let cursor_metrics = CursorMetrics::new(&mut terminal, &screen_geometry, window_state);
-
-        let selected_range = view_geometry.selected_user_range(&self.selection);
-        let selected_range =
-            selected_range.map(|r| r.extend(self.selection.mode().unwrap(), &terminal));
+        let cursor_metrics =
+            CursorMetrics::new(&mut terminal, &screen_geometry, window_state, bidi_hint);
 
         // ADR: Need to keep the time we lock the Terminal as short as possible, so that terminal
         // changes can be pushed to it as fast as possible.
@@ -297,6 +527,8 @@ impl TerminalPresenter {
                     stable_range.start,
                     &self.temporary_line_buf[lines_index.with_len(lines_count)],
                     hyperlink,
+                    selected_range.as_ref(),
+                    rectangular_selection,
                 )?;
 
                 lines_index += lines_count;
@@ -313,27 +545,104 @@ impl TerminalPresenter {
                         .resize_with(len, || Line::new(current_seq_no));
                 }
 
-                view_update.lines(stable_range.start, &self.temporary_line_buf[0..len], None)?;
+                view_update.lines(
+                    stable_range.start,
+                    &self.temporary_line_buf[0..len],
+                    None,
+                    None,
+                    false,
+                )?;
             }
             self.temporary_line_buf.clear();
         }
 
         // Update cursor.
 
+        // The cursor is rendered behind the composed text.
+        let preedit = self.preedit.as_deref().filter(|p| !p.is_empty());
+        view_update.preedit(preedit.zip(cursor_metrics.as_ref()))?;
+        let cursor_metrics = cursor_metrics.map(|mut metrics| {
+            metrics.visual_x += preedit.map_or(0, |p| unicode_column_width(p, None));
+            metrics
+        });
+        self.ime_cursor_area = cursor_metrics
+            .as_ref()
+            .map(|metrics| metrics.rect_px(&view_geometry));
         view_update.cursor(cursor_metrics);
 
         // Update selection
-        {
-            let selection_rows = selected_range.map(|s| s.stable_rows()).unwrap_or_default();
-            let changes_intersect_with_selection =
-                changed_lines.iter().any(|l| selection_rows.contains(l));
 
-            // Clear the selection if changes intersect it and the user does not interact with it.
-            if changes_intersect_with_selection && !self.selection.can_progress() {
-                self.selection.reset();
+        view_update.selection(
+            selected_range,
+            rectangular_selection,
+            &view_geometry,
+            window_state.focused,
+        );
+
+        // Update search matches
+
+        match &mut self.search {
+            Some(search) => {
+                view_update.search_matches(
+                    search.matches_in(view_visible_range.clone()),
+                    search.current(),
+                    &view_geometry,
+                );
+
+                // Show the current match if it's not completely visible.
+                if let Some(current) = search.take_scroll_to_current()
+                    && !current.stable_rows().is_inside(&view_visible_range)
+                {
+                    let rows = self.geometry.rows() as StableRowIndex;
+                    let top = current.start().row - rows / 2;
+                    self.scroll_state =
+                        ScrollState::RestingPixel(self.geometry.stable_px_offset(top) as f64);
+                }
             }
-            view_update.selection(selected_range, &view_geometry);
+            None => view_update.search_matches(&[], None, &view_geometry),
         }
+        view_update.search_bar(
+            self.search.as_ref().map(Search::status).as_deref(),
+            &view_geometry,
+        )?;
+
+        // Update copy mode cursor
+
+        view_update.copy_mode_cursor(self.copy_mode.as_ref().map(|c| c.cursor()), &view_geometry);
+
+        // Update scrollbar
+
+        let scrollbar = ScrollbarGeometry::new(&self.geometry, screen_geometry.buffer_area.clone());
+        self.scrollbar_ticks_outdated |= terminal_updated || scrollbar != self.scrollbar;
+        self.scrollbar = scrollbar;
+
+        // Performance: Querying the zones scans the whole buffer, so the ticks are built only
+        // while the scrollbar is visible.
+        let ticks_outdated = &mut self.scrollbar_ticks_outdated;
+        let (search, terminal) = (&self.search, &self.terminal);
+        view_update.scrollbar(
+            self.scrollbar.as_ref(),
+            self.scrollbar_drag.is_some(),
+            matches!(self.scroll_state, ScrollState::Auto),
+            || {
+                if !*ticks_outdated {
+                    return None;
+                }
+                *ticks_outdated = false;
+                let search_match_rows = search
+                    .iter()
+                    .flat_map(|search| search.matches_in(screen_geometry.buffer_area.clone()))
+                    .map(|m| m.start().row)
+                    .collect();
+                let prompt_rows = Self::query_prompt_rows(&mut terminal.lock())
+                    .inspect_err(|e| warn!("Querying the prompts failed: {e:?}"))
+                    .unwrap_or_default();
+                Some(ScrollbarTicks {
+                    search_match_rows,
+                    prompt_rows,
+                })
+            },
+        );
 
         drop(view_update);
 
@@ -384,20 +693,6 @@ impl TerminalPresenter {
 // Selection
 
 impl TerminalPresenter {
-    /// This way we can upgrade to a triple click / word selection.
-    ///
-    /// Architecture: I don't like this here, and should triple clicks be a mouse gesture, and not
-    /// detected by two double clicks in a row?
-    pub fn selection_in_word_mode_and_selected(&self) -> bool {
-        matches!(
-            self.selection,
-            Selection::Selected {
-                mode: SelectionMode::Word,
-                ..
-            }
-        )
-    }
-
     pub fn selection_begin(&mut self, mode: SelectionMode, hit: PixelPoint) {
         if self.selection != Selection::Unselected {
             warn!(
@@ -407,7 +702,39 @@ impl TerminalPresenter {
         }
         self.selection_clear();
         self.selection
-            .begin(mode, hit, self.view_geometry().hit_test_cell(hit));
+            .begin(mode, hit, self.view_geometry().hit_test_cell(hit), None);
+    }
+
+    /// Begin selecting words, or the match of a smart selection rule at the hit position.
+    ///
+    /// Detail: Takes the terminal, because the caller processes the mouse events while it holds
+    /// the lock.
+    pub fn selection_begin_word(&mut self, hit: PixelPoint, terminal: &Terminal) {
+        self.selection_clear();
+        let pos = self.view_geometry().hit_test_cell(hit);
+        let smart = smart_selection_around(pos, terminal, &self.view.params.config);
+        self.selection.begin(SelectionMode::Word, hit, pos, smart);
+    }
+
+    /// Extend the selection by moving its endpoint nearest to `hit`.
+    ///
+    /// The selection continues to progress like a newly begun one. Returns `false` if there is no
+    /// completed selection.
+    pub fn selection_extend(&mut self, hit: PixelPoint) -> bool {
+        let pos = self.view_geometry().hit_test_cell(hit);
+        self.selection.extend(hit, pos, self.geometry.columns())
+    }
+
+    /// Begin selecting the semantic zone at the hit position.
+    ///
+    /// Detail: Takes the terminal, because the caller processes the mouse events while it holds
+    /// the lock.
+    pub fn selection_begin_zone(&mut self, hit: PixelPoint, terminal: &mut Terminal) {
+        self.selection_clear();
+        let pos = self.view_geometry().hit_test_cell(hit);
+        let zone = zone_around(pos, terminal);
+        self.selection
+            .begin(SelectionMode::Zone, hit, pos, Some(zone));
     }
 
     pub fn selection_clear(&mut self) {
@@ -417,6 +744,10 @@ impl TerminalPresenter {
 
     const PIXEL_TO_SCROLL_VELOCITY_PER_SECOND: f64 = 16.0;
 
+    pub fn selection_mode(&self) -> Option<SelectionMode> {
+        self.selection.mode()
+    }
+
     pub fn selection_can_progress(&self) -> bool {
         self.selection.can_progress()
     }
@@ -457,7 +788,18 @@ impl TerminalPresenter {
     pub fn selected_range(&self) -> Option<SelectedRange> {
         // Architecture: May be a SelectedUserRange can transport SelectionMode?
         let range = self.view_geometry().selected_user_range(&self.selection);
-        range.map(|r| r.extend(self.selection.mode().unwrap(), &self.terminal.lock()))
+        range.map(|r| {
+            r.extend(
+                self.selection.mode().unwrap(),
+                self.selection.resolved(),
+                &self.terminal.lock(),
+                &self.view.params.config,
+            )
+        })
+    }
+
+    pub fn view(&self) -> &TerminalView {
+        &self.view
     }
 
     pub fn view_geometry(&self) -> TerminalViewGeometry {
@@ -465,6 +807,183 @@ impl TerminalPresenter {
     }
 }
 
+// Search
+
+impl TerminalPresenter {
+    pub fn search_begin(&mut self) {
+        if self.search.is_none() {
+            self.search = Some(Search::default());
+            self.scrollbar_ticks_outdated = true;
+        }
+    }
+
+    pub fn search_end(&mut self) {
+        self.search = None;
+        self.scrollbar_ticks_outdated = true;
+    }
+
+    pub fn search(&self) -> Option<&Search> {
+        self.search.as_ref()
+    }
+
+    pub fn search_mut(&mut self) -> Option<&mut Search> {
+        self.search.as_mut()
+    }
+
+    /// `true` if the search needs more updates to scan the buffer.
+    pub fn search_in_progress(&self) -> bool {
+        self.search.as_ref().is_some_and(|s| s.in_progress())
+    }
+}
+
+// Copy Mode
+
+impl TerminalPresenter {
+    /// Enter copy mode with the cursor placed at the terminal's cursor.
+    pub fn copy_mode_begin(&mut self) {
+        if self.copy_mode.is_some() {
+            return;
+        }
+        let cursor = {
+            let terminal = self.terminal.lock();
+            let pos = terminal.cursor_pos();
+            CellPos::new(
+                pos.x.cast_signed(),
+                terminal.screen().visible_row_to_stable_row(pos.y),
+            )
+        };
+        self.copy_mode = Some(CopyMode::new(cursor));
+        self.selection_clear();
+        self.copy_mode_scroll_to_cursor();
+    }
+
+    pub fn copy_mode_end(&mut self) {
+        if self.copy_mode.take().is_some() {
+            self.selection_clear();
+        }
+    }
+
+    pub fn copy_mode(&self) -> Option<&CopyMode> {
+        self.copy_mode.as_ref()
+    }
+
+    pub fn copy_mode_mut(&mut self) -> Option<&mut CopyMode> {
+        self.copy_mode.as_mut()
+    }
+
+    pub fn copy_mode_move(&mut self, motion: CopyModeMotion) {
+        let Some(copy_mode) = &mut self.copy_mode else {
+            return;
+        };
+        copy_mode.move_cursor(motion, &self.terminal.lock(), self.geometry.rows());
+        self.copy_mode_changed();
+    }
+
+    /// Move the copy mode cursor to a position, for example to a search match.
+    pub fn copy_mode_set_cursor(&mut self, pos: CellPos) {
+        let Some(copy_mode) = &mut self.copy_mode else {
+            return;
+        };
+        copy_mode.set_cursor(pos);
+        self.copy_mode_changed();
+    }
+
+    pub fn copy_mode_toggle_selection(&mut self, mode: SelectionMode) {
+        let Some(copy_mode) = &mut self.copy_mode else {
+            return;
+        };
+        copy_mode.toggle_selection(mode);
+        self.copy_mode_changed();
+    }
+
+    pub fn copy_mode_stop_selecting(&mut self) {
+        let Some(copy_mode) = &mut self.copy_mode else {
+            return;
+        };
+        copy_mode.stop_selecting();
+        self.copy_mode_changed();
+    }
+
+    /// Sync the selection and the scroll position with the copy mode.
+    fn copy_mode_changed(&mut self) {
+        let Some(copy_mode) = &self.copy_mode else {
+            return;
+        };
+        self.selection = copy_mode.selection();
+        self.copy_mode_scroll_to_cursor();
+    }
+
+    /// Scroll the least amount of rows needed to show the copy mode cursor.
+    fn copy_mode_scroll_to_cursor(&mut self) {
+        let Some(copy_mode) = &self.copy_mode else {
+            return;
+        };
+        let row = copy_mode.cursor().row;
+        let visible = self.view_geometry().stable_range;
+        let rows = self.geometry.rows() as StableRowIndex;
+
+        let top = if row < visible.start {
+            row
+        } else if row >= visible.start + rows {
+            row - rows + 1
+        } else {
+            return;
+        };
+        self.scroll_state = ScrollState::RestingPixel(self.geometry.stable_px_offset(top) as f64);
+    }
+}
+
+// Scrollbar
+
+impl TerminalPresenter {
+    /// Scroll a page, or start dragging the thumb if the scrollbar is visible and hit.
+    ///
+    /// Returns `true` if the scrollbar was hit.
+    pub fn scrollbar_press(&mut self, pos: PixelPoint) -> bool {
+        let Some(scrollbar) = self
+            .scrollbar
+            .as_ref()
+            .filter(|_| self.view.is_scrollbar_visible())
+        else {
+            return false;
+        };
+
+        let scroll_offset_px = self.view.current_scroll_offset_px();
+        let hit = scrollbar.hit_test(pos, scroll_offset_px);
+        let thumb_top = scrollbar.thumb(scroll_offset_px).min_y();
+
+        let page_px = self.geometry.size_px().height as f64;
+        match hit {
+            None => return false,
+            Some(ScrollbarHit::PageUp) => self.scroll_delta_px(-page_px),
+            Some(ScrollbarHit::PageDown) => self.scroll_delta_px(page_px),
+            Some(ScrollbarHit::Thumb) => self.scrollbar_drag = Some(pos.y - thumb_top),
+        }
+        self.scrollbar_pressed = true;
+        true
+    }
+
+    /// Move the thumb while it's dragged.
+    ///
+    /// Returns `true` if the thumb is being dragged.
+    pub fn scrollbar_drag_to(&mut self, pos: PixelPoint) -> bool {
+        let (Some(grabbed_at), Some(scrollbar)) = (self.scrollbar_drag, &self.scrollbar) else {
+            return false;
+        };
+        let scroll_offset_px = scrollbar.thumb_top_to_scroll_offset_px(pos.y - grabbed_at);
+        self.scroll_state = ScrollState::RestingPixel(scroll_offset_px.round());
+        true
+    }
+
+    /// Stop dragging the thumb.
+    ///
+    /// Returns `true` if the press hit the scrollbar.
+    pub fn scrollbar_release(&mut self) -> bool {
+        self.scrollbar_drag = None;
+        std::mem::take(&mut self.scrollbar_pressed)
+    }
+}
+
 // Selection Scrolling
 
 impl TerminalPresenter {
@@ -512,6 +1031,8 @@ enum ScrollState {
     RestingPixel(f64),
     /// The selection is currently controlling the scrolling with a particular velocity.
     SelectionScroll(SelectionScroller),
+    /// A trackpad gesture is scrolling, or the scrolling continues after it.
+    Kinetic(KineticScroller),
 }
 
 #[derive(Debug)]
@@ -520,6 +1041,12 @@ struct SelectionScroller {
     time_scale: TimeScale,
 }
 
+#[derive(Debug)]
+struct KineticScroller {
+    scroller: TerminalScroller,
+    time_scale: TimeScale,
+}
+
 impl ScrollState {
     fn apply_to_view(
         &mut self,
@@ -544,6 +1071,16 @@ impl ScrollState {
                     geometry.clamp_px_offset(screen_geometry.buffer_area.clone(), final_px_offset);
                 view.scroll_to_px(final_px_offset_clamped);
             }
+            ScrollState::Kinetic(kinetic) => {
+                let px_offset = kinetic.scroller.proceed(kinetic.time_scale.scale_seconds());
+                let px_offset_clamped =
+                    geometry.clamp_px_offset(screen_geometry.buffer_area.clone(), px_offset);
+                kinetic.scroller.limit_to(px_offset_clamped);
+                view.scroll_to_px(px_offset_clamped);
+                if kinetic.scroller.is_resting() {
+                    *self = ScrollState::RestingPixel(px_offset_clamped);
+                }
+            }
         }
     }
 }
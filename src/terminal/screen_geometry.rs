@@ -16,7 +16,6 @@ pub struct ScreenGeometry {
     // The range the terminal has line data for.
     pub buffer_area: Range<StableRowIndex>,
 
-    #[allow(unused)]
     pub columns: usize,
 }
 
@@ -0,0 +1,134 @@
+//! The scrollbar overlay on the right edge of the view.
+//!
+//! The track represents the whole buffer area. The thumb covers the rows that are visible.
+use std::ops::Range;
+
+use massive_geometry::{Point, Rect, Size};
+use wezterm_term::StableRowIndex;
+
+use crate::{terminal::TerminalGeometry, view_geometry::PixelPoint};
+
+/// The minimum height of the thumb in pixels, so that it can be grabbed in large buffers.
+const MIN_THUMB_HEIGHT_PX: f64 = 16.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollbarHit {
+    /// The track above the thumb.
+    PageUp,
+    /// The track below the thumb.
+    PageDown,
+    Thumb,
+}
+
+/// The rows marked on the track.
+#[derive(Debug, Default)]
+pub struct ScrollbarTicks {
+    pub search_match_rows: Vec<StableRowIndex>,
+    pub prompt_rows: Vec<StableRowIndex>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScrollbarGeometry {
+    track: Rect,
+    buffer_area: Range<StableRowIndex>,
+    line_height_px: f64,
+    rows: usize,
+}
+
+impl ScrollbarGeometry {
+    /// Returns `None` if the buffer has no rows outside the view, like the alternate screen.
+    pub fn new(geometry: &TerminalGeometry, buffer_area: Range<StableRowIndex>) -> Option<Self> {
+        if buffer_area.len() <= geometry.rows() {
+            return None;
+        }
+
+        let size_px = geometry.size_px();
+        // Detail: The width is derived from the cell width, so that it scales with the font and
+        // the display's scale factor.
+        let width = (geometry.cell_size_px.width as f64 / 2.).max(4.);
+        let track = Rect::new(
+            Point::new(size_px.width as f64 - width, 0.),
+            Size::new(width, size_px.height as f64),
+        );
+
+        Some(Self {
+            track,
+            buffer_area,
+            line_height_px: geometry.line_height_px() as f64,
+            rows: geometry.rows(),
+        })
+    }
+
+    pub fn track(&self) -> Rect {
+        self.track
+    }
+
+    /// The thumb at a scroll offset of the view.
+    pub fn thumb(&self, scroll_offset_px: f64) -> Rect {
+        let scroll_rows = scroll_offset_px / self.line_height_px - self.buffer_area.start as f64;
+        Rect::new(
+            Point::new(self.track.min_x(), self.thumb_top(scroll_rows)),
+            Size::new(self.track.width(), self.thumb_height()),
+        )
+    }
+
+    /// The vertical position of a row on the track.
+    ///
+    /// Detail: This is the center of the thumb when the row is in the center of the view, so that
+    /// the thumb covers the rows it marks.
+    pub fn row_y(&self, row: StableRowIndex) -> f64 {
+        let scroll_rows = (row - self.buffer_area.start) as f64 - self.rows as f64 / 2.;
+        self.thumb_top(scroll_rows) + self.thumb_height() / 2.
+    }
+
+    pub fn hit_test(&self, pos: PixelPoint, scroll_offset_px: f64) -> Option<ScrollbarHit> {
+        if !(self.track.min_x()..self.track.max_x()).contains(&pos.x)
+            || !(self.track.min_y()..self.track.max_y()).contains(&pos.y)
+        {
+            return None;
+        }
+
+        let thumb = self.thumb(scroll_offset_px);
+        Some(if pos.y < thumb.min_y() {
+            ScrollbarHit::PageUp
+        } else if pos.y >= thumb.max_y() {
+            ScrollbarHit::PageDown
+        } else {
+            ScrollbarHit::Thumb
+        })
+    }
+
+    /// The scroll offset that places the top of the thumb at a vertical position on the track.
+    pub fn thumb_top_to_scroll_offset_px(&self, thumb_top: f64) -> f64 {
+        let scroll_rows = thumb_top / self.thumb_travel() * self.scrollable_rows();
+        (scroll_rows + self.buffer_area.start as f64) * self.line_height_px
+    }
+
+    /// The top of the thumb when the view is scrolled by a number of rows from the top of the
+    /// buffer.
+    fn thumb_top(&self, scroll_rows: f64) -> f64 {
+        (scroll_rows / self.scrollable_rows()).clamp(0., 1.) * self.thumb_travel()
+    }
+
+    fn thumb_height(&self) -> f64 {
+        (self.rows as f64 * self.px_per_row()).max(MIN_THUMB_HEIGHT_PX)
+    }
+
+    /// The distance the top of the thumb moves from the top to the bottom of the buffer.
+    ///
+    /// Detail: If the thumb is enlarged to its minimum height, this is shorter than the track
+    /// rows represent.
+    fn thumb_travel(&self) -> f64 {
+        // Robustness: Views that are not higher than the minimum thumb would divide by zero.
+        (self.track.height() - self.thumb_height()).max(1.)
+    }
+
+    /// The number of rows the view can scroll.
+    fn scrollable_rows(&self) -> f64 {
+        (self.buffer_area.len() - self.rows) as f64
+    }
+
+    fn px_per_row(&self) -> f64 {
+        self.track.height() / self.buffer_area.len() as f64
+    }
+}
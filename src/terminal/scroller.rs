@@ -1,109 +1,323 @@
-//! Scrolling control for terminals.
-use std::time::Duration;
+//! Kinetic scrolling for terminals.
+//!
+//! While a trackpad gesture is active, the scroll offset follows the deltas and the velocity of the
+//! gesture is tracked. After the gesture ended, the offset continues with an exponentially decaying
+//! velocity and comes to rest on a whole line.
+//!
+//! Platforms that supply their own momentum (macOS) continue with a second gesture after the fingers
+//! were lifted. Its deltas are followed instead.
+use std::time::{Duration, Instant};
 
-use massive_animation::{Animated, Interpolation};
-use massive_shell::Scene;
+/// The time after which the velocity has decayed to ~37%.
+const DECAY_TIME_CONSTANT: Duration = Duration::from_millis(325);
+/// Deltas that are further apart don't contribute to the velocity.
+///
+/// Detail: If the last delta is older when the gesture ends, the fingers rested before they were
+/// lifted, and the scroller does not continue.
+const VELOCITY_SAMPLE_WINDOW: Duration = Duration::from_millis(100);
+/// The weight of the latest sample in the tracked velocity.
+const VELOCITY_SMOOTHING: f64 = 0.8;
+/// A gesture that begins this soon after the release is the momentum of the platform, not a new
+/// gesture of the user.
+const MOMENTUM_START_WINDOW: Duration = Duration::from_millis(50);
+/// The scroller snaps to the resting point when it gets closer than this.
+const RESTING_DISTANCE_PX: f64 = 0.5;
 
-#[allow(unused)]
-#[derive(Debug)]
-pub struct TerminalScroller {
-    /// The duration until we reach the final scrolling speed.
-    phase_in_duration: Duration,
-    /// The duration until we reach the resting point.
-    phase_out_duration: Duration,
-
-    /// The current velocity in pixels per second.
-    velocity: Animated<f64>,
-
-    /// The current scroll offset, either manually updated by the current velocity or animated.
-    scroll_offset: Animated<f64>,
-
-    state: ScrollAnimationState,
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+    Resting,
+    /// The offset follows the deltas of the gesture.
+    Tracking {
+        last_delta: Instant,
+    },
+    /// The velocity decays until the offset reaches the resting point.
+    Coasting {
+        resting_px: f64,
+        released: Instant,
+    },
+    /// The offset follows the momentum deltas of the platform.
+    Momentum,
 }
 
-#[allow(unused)]
-#[derive(Debug)]
-enum ScrollAnimationState {
-    NoScrolling,
-    PhasingInOrScrolling,
-    PhasingOut,
+#[derive(Debug, Clone)]
+pub struct TerminalScroller {
+    line_height_px: f64,
+    offset_px: f64,
+    /// The current velocity in pixels per second.
+    velocity: f64,
+    phase: Phase,
 }
 
-#[allow(unused)]
 impl TerminalScroller {
-    pub fn new(scene: &Scene, phase_in_duration: Duration, phase_out_duration: Duration) -> Self {
-        let velocity = scene.animated(0.0);
-        let scroll_offset = scene.animated(0.0);
-
+    pub fn new(offset_px: f64, line_height_px: f64) -> Self {
         Self {
-            phase_in_duration,
-            phase_out_duration,
-            velocity,
-            scroll_offset,
-            state: ScrollAnimationState::NoScrolling,
+            line_height_px,
+            offset_px,
+            velocity: 0.0,
+            phase: Phase::Resting,
         }
     }
 
-    /// Set a new velocity and start scrolling if not currently so.
-    pub fn set_velocity(&mut self, pixels_per_second: f64) {
-        self.velocity.animate(
-            pixels_per_second,
-            self.phase_in_duration,
-            Interpolation::CubicOut,
-        );
-        self.state = ScrollAnimationState::PhasingInOrScrolling;
-    }
-
-    // Architecture: May embed the transition here in the type system by taking self and return a
-    // different type?
-    pub fn rest(&mut self, resting_point: f64) {
-        // Research: How do we combine these two animations?
-        self.velocity
-            .animate(0.0, self.phase_out_duration, Interpolation::CubicIn);
-        self.scroll_offset.animate(
-            resting_point,
-            self.phase_out_duration,
-            Interpolation::CubicIn,
-        );
-        self.state = ScrollAnimationState::PhasingOut;
+    pub fn offset_px(&self) -> f64 {
+        self.offset_px
+    }
+
+    pub fn is_resting(&self) -> bool {
+        self.phase == Phase::Resting
     }
 
-    /// Proceed the scrolling animation and return the final scroll offset.
+    /// A gesture begins.
     ///
-    /// Returns `None` if no scrolling is active and / or the resting point has been returned
-    /// before.
-    pub fn proceed(&mut self) -> Option<f64> {
-        match self.state {
-            ScrollAnimationState::NoScrolling => None,
-            ScrollAnimationState::PhasingInOrScrolling => {
-                let velocity = self.velocity.value();
-                let new_scroll_offset = self.scroll_offset.value() + velocity;
-                self.scroll_offset.animate(
-                    new_scroll_offset,
-                    Duration::ZERO,
-                    Interpolation::Linear,
-                );
-                let effective_scroll_offset = self.scroll_offset.value();
-                Some(effective_scroll_offset)
+    /// If it begins right after the release, it's the momentum of the platform and coasting stops.
+    pub fn begin(&mut self, now: Instant) {
+        if let Phase::Coasting { released, .. } = self.phase
+            && now.saturating_duration_since(released) <= MOMENTUM_START_WINDOW
+        {
+            self.velocity = 0.0;
+            self.phase = Phase::Momentum;
+        }
+    }
+
+    /// Move the offset by a delta of the gesture.
+    ///
+    /// This starts tracking if the scroller is resting or coasting.
+    pub fn track(&mut self, delta_px: f64, now: Instant) {
+        match self.phase {
+            Phase::Momentum => {
+                self.offset_px += delta_px;
+                return;
+            }
+            Phase::Tracking { last_delta } => {
+                let elapsed = now.saturating_duration_since(last_delta);
+                if elapsed > VELOCITY_SAMPLE_WINDOW {
+                    self.velocity = 0.0;
+                } else if !elapsed.is_zero() {
+                    let sample = delta_px / elapsed.as_secs_f64();
+                    self.velocity =
+                        VELOCITY_SMOOTHING * sample + (1.0 - VELOCITY_SMOOTHING) * self.velocity;
+                }
+                // Precision: Deltas that arrive at the same instant are not part of the velocity.
             }
-            ScrollAnimationState::PhasingOut => {
-                if !self.velocity.is_animating() && !self.scroll_offset.is_animating() {
-                    return None;
+            Phase::Resting | Phase::Coasting { .. } => self.velocity = 0.0,
+        }
+
+        self.offset_px += delta_px;
+        self.phase = Phase::Tracking { last_delta: now };
+    }
+
+    /// End the gesture and continue with the tracked velocity.
+    ///
+    /// At the end of the platform's momentum, this moves to the nearest line.
+    pub fn release(&mut self, now: Instant) {
+        match self.phase {
+            Phase::Tracking { last_delta } => {
+                if now.saturating_duration_since(last_delta) > VELOCITY_SAMPLE_WINDOW {
+                    self.velocity = 0.0;
                 }
+            }
+            Phase::Momentum => self.velocity = 0.0,
+            Phase::Resting | Phase::Coasting { .. } => return,
+        }
+
+        let time_constant = DECAY_TIME_CONSTANT.as_secs_f64();
+        let projected_px = self.offset_px + self.velocity * time_constant;
+        let resting_px = (projected_px / self.line_height_px).round() * self.line_height_px;
+        // Detail: The velocity is adjusted so that the decay ends exactly on the resting line. If
+        // there is no velocity, this moves to the nearest line.
+        self.velocity = (resting_px - self.offset_px) / time_constant;
+        self.phase = Phase::Coasting {
+            resting_px,
+            released: now,
+        };
+    }
 
-                // Research: This implementation is wrong, this is somehow a blend of velocity going
-                // down and the final scroll offset taking hold.
-                let velocity = self.velocity.value();
-                let new_scroll_offset = self.scroll_offset.value() + velocity;
-                self.scroll_offset.animate(
-                    new_scroll_offset,
-                    Duration::ZERO,
-                    Interpolation::Linear,
-                );
-                let effective_scroll_offset = self.scroll_offset.value();
-
-                Some(effective_scroll_offset)
+    /// Proceed coasting by the elapsed time and return the offset.
+    ///
+    /// Detail: The decay is integrated exactly, so the result does not depend on how the time is
+    /// split into frames.
+    pub fn proceed(&mut self, elapsed_seconds: f64) -> f64 {
+        if let Phase::Coasting { resting_px, .. } = self.phase {
+            let time_constant = DECAY_TIME_CONSTANT.as_secs_f64();
+            let decay = (-elapsed_seconds / time_constant).exp();
+            self.offset_px += self.velocity * time_constant * (1.0 - decay);
+            self.velocity *= decay;
+
+            if (resting_px - self.offset_px).abs() < RESTING_DISTANCE_PX {
+                self.offset_px = resting_px;
+                self.velocity = 0.0;
+                self.phase = Phase::Resting;
             }
         }
+
+        self.offset_px
+    }
+
+    /// Move the offset to a limit, for example the top of the scrollback buffer.
+    ///
+    /// Coasting stops there.
+    pub fn limit_to(&mut self, offset_px: f64) {
+        if offset_px == self.offset_px {
+            return;
+        }
+        self.offset_px = offset_px;
+        self.velocity = 0.0;
+        if let Phase::Coasting { .. } = self.phase {
+            self.phase = Phase::Resting;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINE_HEIGHT: f64 = 20.0;
+    const FRAME: Duration = Duration::from_millis(16);
+
+    #[test]
+    fn tracking_follows_the_deltas() {
+        let mut scroller = TerminalScroller::new(100.0, LINE_HEIGHT);
+        let now = Instant::now();
+        scroller.track(5.0, now);
+        scroller.track(7.5, now + FRAME);
+        assert_eq!(scroller.offset_px(), 112.5);
+        // Tracking does not proceed on its own.
+        assert_eq!(scroller.proceed(1.0), 112.5);
+    }
+
+    #[test]
+    fn fling_continues_after_release() {
+        let mut scroller = TerminalScroller::new(0.0, LINE_HEIGHT);
+        fling(&mut scroller, 10.0, 10);
+        let released_at = scroller.offset_px();
+        assert_eq!(released_at, 100.0);
+
+        coast(&mut scroller, FRAME.as_secs_f64());
+        // 10px per 16ms are 625px/s, which continue for about 200px.
+        assert!(scroller.offset_px() > released_at + 150.0);
+    }
+
+    #[test]
+    fn fling_upwards_continues_upwards() {
+        let mut scroller = TerminalScroller::new(1000.0, LINE_HEIGHT);
+        fling(&mut scroller, -10.0, 10);
+        coast(&mut scroller, FRAME.as_secs_f64());
+        assert!(scroller.offset_px() < 900.0 - 150.0);
+    }
+
+    #[test]
+    fn rests_on_a_line() {
+        let mut scroller = TerminalScroller::new(3.0, LINE_HEIGHT);
+        fling(&mut scroller, 7.0, 5);
+        coast(&mut scroller, FRAME.as_secs_f64());
+        assert_eq!(scroller.offset_px() % LINE_HEIGHT, 0.0);
+    }
+
+    #[test]
+    fn rested_fingers_do_not_fling() {
+        let mut scroller = TerminalScroller::new(0.0, LINE_HEIGHT);
+        let now = Instant::now();
+        scroller.track(10.0, now);
+        scroller.track(10.0, now + FRAME);
+        scroller.release(now + FRAME + Duration::from_millis(500));
+        coast(&mut scroller, FRAME.as_secs_f64());
+        // Only moves to the nearest line.
+        assert_eq!(scroller.offset_px(), 20.0);
+    }
+
+    #[test]
+    fn coasting_is_frame_rate_independent() {
+        let mut at_60 = TerminalScroller::new(0.0, LINE_HEIGHT);
+        let mut at_120 = at_60.clone();
+        fling(&mut at_60, 10.0, 10);
+        fling(&mut at_120, 10.0, 10);
+
+        for _ in 0..10 {
+            at_60.proceed(1.0 / 60.0);
+            at_120.proceed(1.0 / 120.0);
+            at_120.proceed(1.0 / 120.0);
+        }
+        assert!((at_60.offset_px() - at_120.offset_px()).abs() < 1e-9);
+
+        let frames_at_60 = coast(&mut at_60, 1.0 / 60.0);
+        let frames_at_120 = coast(&mut at_120, 1.0 / 120.0);
+        assert_eq!(at_60.offset_px(), at_120.offset_px());
+        assert!(frames_at_120.abs_diff(frames_at_60 * 2) <= 2);
+    }
+
+    #[test]
+    fn limit_stops_coasting() {
+        let mut scroller = TerminalScroller::new(100.0, LINE_HEIGHT);
+        fling(&mut scroller, -10.0, 10);
+        scroller.proceed(FRAME.as_secs_f64());
+        scroller.limit_to(0.0);
+        assert!(scroller.is_resting());
+        assert_eq!(scroller.proceed(1.0), 0.0);
+    }
+
+    #[test]
+    fn platform_momentum_replaces_coasting() {
+        let mut scroller = TerminalScroller::new(0.0, LINE_HEIGHT);
+        let released = fling(&mut scroller, 10.0, 10);
+        let coasted = scroller.proceed(0.004);
+
+        // macOS sends the momentum as a second gesture right after the fingers were lifted.
+        let start = released + Duration::from_millis(8);
+        scroller.begin(start);
+        for (frame, delta) in [8.0, 6.0, 4.0, 2.0, 1.0].into_iter().enumerate() {
+            scroller.track(delta, start + FRAME * frame as u32);
+            // Momentum deltas are followed and there is no coasting on top of them.
+            assert_eq!(scroller.proceed(FRAME.as_secs_f64()), scroller.offset_px());
+        }
+        let momentum_end = scroller.offset_px();
+        assert!((momentum_end - (coasted + 21.0)).abs() < 1e-9);
+
+        // The end of the momentum does not coast again, it only moves to the nearest line.
+        scroller.release(start + FRAME * 5);
+        coast(&mut scroller, FRAME.as_secs_f64());
+        assert_eq!(
+            scroller.offset_px(),
+            (momentum_end / LINE_HEIGHT).round() * LINE_HEIGHT
+        );
+    }
+
+    #[test]
+    fn late_gesture_is_not_momentum() {
+        let mut scroller = TerminalScroller::new(0.0, LINE_HEIGHT);
+        let released = fling(&mut scroller, 10.0, 10);
+        scroller.begin(released + Duration::from_millis(500));
+        scroller.proceed(FRAME.as_secs_f64());
+        // Still coasting.
+        assert!(scroller.offset_px() > 100.0);
+    }
+
+    #[test]
+    fn new_gesture_interrupts_coasting() {
+        let mut scroller = TerminalScroller::new(0.0, LINE_HEIGHT);
+        let released = fling(&mut scroller, 10.0, 10);
+        let coasted = scroller.proceed(FRAME.as_secs_f64());
+        scroller.track(1.0, released + Duration::from_millis(50));
+        assert_eq!(scroller.proceed(1.0), coasted + 1.0);
+    }
+
+    /// Track a gesture of `frames` deltas, one per frame, and release it.
+    fn fling(scroller: &mut TerminalScroller, delta_px: f64, frames: u32) -> Instant {
+        let start = Instant::now();
+        for frame in 0..frames {
+            scroller.track(delta_px, start + FRAME * frame);
+        }
+        let end = start + FRAME * (frames - 1);
+        scroller.release(end);
+        end
+    }
+
+    fn coast(scroller: &mut TerminalScroller, elapsed_seconds: f64) -> usize {
+        let mut frames = 0;
+        while !scroller.is_resting() {
+            scroller.proceed(elapsed_seconds);
+            frames += 1;
+            assert!(frames < 100_000, "Scroller does not come to rest");
+        }
+        frames
     }
 }
@@ -0,0 +1,351 @@
+//! Searching the scrollback buffer.
+//!
+//! Matches are searched in logical lines, so that they can span wrapped lines. The buffer is
+//! scanned incrementally, a limited number of rows per update, so that the terminal is not locked
+//! for long when the scrollback is large.
+use std::ops::Range;
+
+use log::debug;
+use rangeset::RangeSet;
+use regex::{Regex, RegexBuilder};
+use wezterm_term::{StableRowIndex, Terminal};
+
+use crate::{
+    range_ops::RangeOps,
+    terminal::{LineText, SelectedRange, click_range_to_selected_range, get_logical_lines},
+};
+
+/// The maximum number of rows scanned per update.
+///
+/// Performance: Scanning clones the lines, this should be tuned by measuring the time the terminal
+/// is locked.
+const SCAN_ROWS_PER_UPDATE: usize = 2000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SearchKind {
+    #[default]
+    Plain,
+    CaseInsensitive,
+    Regex,
+}
+
+impl SearchKind {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Plain => Self::CaseInsensitive,
+            Self::CaseInsensitive => Self::Regex,
+            Self::Regex => Self::Plain,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Plain => "Plain",
+            Self::CaseInsensitive => "Case Insensitive",
+            Self::Regex => "Regex",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Search {
+    query: String,
+    kind: SearchKind,
+    /// `None` if the query is empty or not a valid regular expression.
+    regex: Option<Regex>,
+
+    /// The matches found so far, ordered by their start position.
+    ///
+    /// Detail: Matches never overlap, so they are ordered by their end positions, too.
+    matches: Vec<SelectedRange>,
+    /// The rows scanned so far, `None` if scanning has not started yet.
+    scanned: Option<Range<StableRowIndex>>,
+    /// Scanned rows that changed and need to be scanned again.
+    rescan: RangeSet<StableRowIndex>,
+    complete: bool,
+    /// Set if matches were found or removed since the last time they were taken.
+    matches_changed: bool,
+
+    /// The match the user navigates from.
+    current: Option<SelectedRange>,
+    /// Set if the view should show the current match.
+    scroll_to_current: bool,
+}
+
+impl Search {
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn kind(&self) -> SearchKind {
+        self.kind
+    }
+
+    pub fn push_char(&mut self, c: char) {
+        self.query.push(c);
+        self.restart();
+    }
+
+    pub fn pop_char(&mut self) {
+        self.query.pop();
+        self.restart();
+    }
+
+    pub fn cycle_kind(&mut self) {
+        self.kind = self.kind.next();
+        self.restart();
+    }
+
+    /// Forget all matches and scan the buffer again.
+    pub fn restart(&mut self) {
+        self.regex = self.compile();
+        self.matches.clear();
+        self.scanned = None;
+        self.rescan = RangeSet::new();
+        self.complete = false;
+        self.matches_changed = true;
+        self.current = None;
+        self.scroll_to_current = false;
+    }
+
+    fn compile(&self) -> Option<Regex> {
+        if self.query.is_empty() {
+            return None;
+        }
+        let pattern = match self.kind {
+            SearchKind::Plain | SearchKind::CaseInsensitive => regex::escape(&self.query),
+            SearchKind::Regex => self.query.clone(),
+        };
+        RegexBuilder::new(&pattern)
+            .case_insensitive(self.kind == SearchKind::CaseInsensitive)
+            .build()
+            .inspect_err(|e| debug!("Invalid search pattern: {e}"))
+            .ok()
+    }
+
+    /// `true` if there are rows left to scan.
+    pub fn in_progress(&self) -> bool {
+        self.regex.is_some() && (!self.complete || !self.rescan.is_empty())
+    }
+
+    /// Scan the rows that changed again.
+    ///
+    /// Detail: Matches may span wrapped lines, so the complete logical lines that contain the
+    /// changed rows are scanned again.
+    pub fn invalidate(&mut self, terminal: &Terminal, changed_rows: &RangeSet<StableRowIndex>) {
+        let Some(scanned) = &self.scanned else {
+            return;
+        };
+        for rows in changed_rows.iter() {
+            let Some(rows) = rows.intersect(scanned) else {
+                continue;
+            };
+            terminal
+                .screen()
+                .for_each_logical_line_in_stable_range(rows, |logical_rows, _| {
+                    self.rescan.add_range(logical_rows);
+                    true
+                });
+        }
+    }
+
+    /// Scan the next rows of the buffer.
+    ///
+    /// Rows added at the bottom are scanned first, then the scan continues upwards.
+    pub fn scan(&mut self, terminal: &Terminal, buffer_area: Range<StableRowIndex>) {
+        let Some(regex) = &self.regex else {
+            return;
+        };
+
+        let mut scanned = match self.scanned.clone() {
+            // Detail: The buffer only shrinks at the bottom when it gets cleared.
+            Some(scanned) if scanned.end <= buffer_area.end => scanned,
+            _ => buffer_area.end..buffer_area.end,
+        };
+
+        // Forget the matches of the rows that left the scrollback buffer.
+        if scanned.start < buffer_area.start {
+            scanned = buffer_area.start..scanned.end.max(buffer_area.start);
+            self.matches.retain(|m| m.start().row >= buffer_area.start);
+            self.matches_changed = true;
+            if self
+                .current
+                .is_some_and(|m| m.start().row < buffer_area.start)
+            {
+                self.current = None;
+            }
+        }
+
+        // Scan the changed rows first, they are usually the ones on the screen.
+        //
+        // Performance: Changed rows are not limited by the budget, there are usually not more
+        // than the rows of the screen.
+        let rescan = std::mem::take(&mut self.rescan);
+        for rows in rescan.iter() {
+            if let Some(rows) = rows.intersect(&scanned) {
+                Self::remove_matches_starting_in(&mut self.matches, rows.clone());
+                let found = Self::scan_rows(regex, terminal, rows);
+                Self::merge_matches(&mut self.matches, found);
+                self.matches_changed = true;
+            }
+        }
+
+        let mut budget = SCAN_ROWS_PER_UPDATE as StableRowIndex;
+
+        if scanned.end < buffer_area.end {
+            let rows = scanned.end..(scanned.end + budget).min(buffer_area.end);
+            let found = Self::scan_rows(regex, terminal, rows.clone());
+            self.matches_changed |= !found.is_empty();
+            Self::merge_matches(&mut self.matches, found);
+            budget -= rows.len() as StableRowIndex;
+            scanned.end = rows.end;
+        }
+
+        if budget > 0 && scanned.start > buffer_area.start {
+            let rows = (scanned.start - budget).max(buffer_area.start)..scanned.start;
+            let found = Self::scan_rows(regex, terminal, rows.clone());
+            self.matches_changed |= !found.is_empty();
+            Self::merge_matches(&mut self.matches, found);
+            scanned.start = rows.start;
+        }
+
+        self.complete = scanned == buffer_area;
+        self.scanned = Some(scanned);
+
+        // Show the bottom-most match as soon as one was found.
+        if self.current.is_none()
+            && let Some(last) = self.matches.last()
+        {
+            self.current = Some(*last);
+            self.scroll_to_current = true;
+        }
+    }
+
+    /// Find the matches in the logical lines that start in `rows`, ordered by their start
+    /// position.
+    fn scan_rows(
+        regex: &Regex,
+        terminal: &Terminal,
+        rows: Range<StableRowIndex>,
+    ) -> Vec<SelectedRange> {
+        let mut matches = Vec::new();
+        for logical in get_logical_lines(terminal, rows.clone()) {
+            // Detail: Logical lines that start above are scanned with the rows they start in.
+            if !rows.contains(&logical.first_row) {
+                continue;
+            }
+            let line_text = LineText::new(&logical.logical);
+            for found in regex.find_iter(&line_text.text) {
+                if let Some(cells) = line_text.cell_range(found.range()) {
+                    matches.push(click_range_to_selected_range(&logical, cells));
+                }
+            }
+        }
+        matches
+    }
+
+    /// Insert the matches found in rows that have no matches yet.
+    ///
+    /// Detail: Matches found in a range of rows start before or after all the other matches, so
+    /// they are inserted at one position.
+    fn merge_matches(matches: &mut Vec<SelectedRange>, found: Vec<SelectedRange>) {
+        let Some(first) = found.first() else {
+            return;
+        };
+        let index = matches.partition_point(|m| m.start() < first.start());
+        matches.splice(index..index, found);
+    }
+
+    fn remove_matches_starting_in(matches: &mut Vec<SelectedRange>, rows: Range<StableRowIndex>) {
+        let start = matches.partition_point(|m| m.start().row < rows.start);
+        let end = matches.partition_point(|m| m.start().row < rows.end);
+        matches.drain(start..end);
+    }
+
+    pub fn current(&self) -> Option<SelectedRange> {
+        self.current
+    }
+
+    /// The query, the kind, and the number of matches found so far.
+    ///
+    /// A `+` marks a count that grows while the buffer is scanned.
+    pub fn status(&self) -> String {
+        let found = if self.query.is_empty() {
+            String::new()
+        } else if self.regex.is_none() {
+            "  Invalid pattern".to_string()
+        } else {
+            let count = self.matches.len();
+            let more = if self.in_progress() { "+" } else { "" };
+            let position = self.current.and_then(|current| {
+                self.matches
+                    .binary_search_by(|m| m.start().cmp(current.start()))
+                    .ok()
+            });
+            match position {
+                Some(index) => format!("  {}/{count}{more}", index + 1),
+                None => format!("  {count}{more} matches"),
+            }
+        };
+        format!("Search ({}): {}{found}", self.kind.name(), self.query)
+    }
+
+    /// Select the next match below the current one, wraps around.
+    pub fn next(&mut self) {
+        let next = match self.current {
+            Some(current) => {
+                let index = self
+                    .matches
+                    .partition_point(|m| m.start() <= current.start());
+                self.matches.get(index).or(self.matches.first())
+            }
+            None => self.matches.last(),
+        };
+        self.select(next.copied());
+    }
+
+    /// Select the previous match above the current one, wraps around.
+    pub fn previous(&mut self) {
+        let previous = match self.current {
+            Some(current) => {
+                let index = self
+                    .matches
+                    .partition_point(|m| m.start() < current.start());
+                index
+                    .checked_sub(1)
+                    .and_then(|i| self.matches.get(i))
+                    .or(self.matches.last())
+            }
+            None => self.matches.last(),
+        };
+        self.select(previous.copied());
+    }
+
+    fn select(&mut self, selected: Option<SelectedRange>) {
+        if selected.is_some() {
+            self.current = selected;
+            self.scroll_to_current = true;
+        }
+    }
+
+    /// Returns the current match if the view should scroll to it.
+    pub fn take_scroll_to_current(&mut self) -> Option<SelectedRange> {
+        if !self.scroll_to_current {
+            return None;
+        }
+        self.scroll_to_current = false;
+        self.current
+    }
+
+    /// Returns `true` if matches were found or removed since the last call.
+    pub fn take_matches_changed(&mut self) -> bool {
+        std::mem::take(&mut self.matches_changed)
+    }
+
+    /// The matches that intersect with the rows.
+    pub fn matches_in(&self, rows: Range<StableRowIndex>) -> &[SelectedRange] {
+        let start = self.matches.partition_point(|m| m.end().row < rows.start);
+        let end = self.matches.partition_point(|m| m.start().row < rows.end);
+        &self.matches[start..end.max(start)]
+    }
+}
@@ -1,11 +1,13 @@
 use std::ops::Range;
 
 use log::{error, warn};
-use wezterm_term::{DoubleClickRange, StableRowIndex, Terminal};
+use unicode_segmentation::UnicodeSegmentation;
+use wezterm_term::{DoubleClickRange, Line, StableRowIndex, Terminal};
 
 use crate::{
+    config::{Config, SmartSelectionRules, WordSelection},
     range_ops::{RangeOps, WithLength},
-    terminal::{CellPos, LogicalLine, get_logical_lines},
+    terminal::{CellPos, LineText, LogicalLine, get_logical_lines},
     view_geometry::PixelPoint,
 };
 
@@ -14,6 +16,12 @@ pub enum SelectionMode {
     Cell,
     Word,
     Line,
+    /// A block of cells, the columns between the start and the end on every row.
+    Rectangular,
+    /// A semantic zone (OSC 133) like a prompt, its input, or its output.
+    ///
+    /// Zones are resolved when the selection begins.
+    Zone,
 }
 
 #[derive(Debug, Default, PartialEq)]
@@ -26,11 +34,15 @@ pub enum Selection {
         mode: SelectionMode,
         from: CellPos,
         to: PixelPoint,
+        /// The cells resolved at the press that began the selection: the match of a smart
+        /// selection rule in word mode, or the semantic zone in zone mode.
+        resolved: Option<SelectedRange>,
     },
     Selected {
         mode: SelectionMode,
         from: CellPos,
         to: CellPos,
+        resolved: Option<SelectedRange>,
     },
 }
 
@@ -43,11 +55,26 @@ impl Selection {
         }
     }
 
-    pub fn begin(&mut self, mode: SelectionMode, hit: PixelPoint, pos: CellPos) {
+    pub fn begin(
+        &mut self,
+        mode: SelectionMode,
+        hit: PixelPoint,
+        pos: CellPos,
+        resolved: Option<SelectedRange>,
+    ) {
         *self = Self::Selecting {
             mode,
             from: pos,
             to: hit,
+            resolved,
+        }
+    }
+
+    /// The cells resolved when the selection began.
+    pub fn resolved(&self) -> Option<SelectedRange> {
+        match *self {
+            Self::Unselected => None,
+            Self::Selecting { resolved, .. } | Self::Selected { resolved, .. } => resolved,
         }
     }
 
@@ -58,11 +85,15 @@ impl Selection {
     pub fn progress(&mut self, end: PixelPoint) {
         *self = match &self {
             Self::Selecting {
-                mode, from: start, ..
+                mode,
+                from: start,
+                resolved,
+                ..
             } => Self::Selecting {
                 mode: *mode,
                 from: *start,
                 to: end,
+                resolved: *resolved,
             },
             _ => {
                 // This happens when the selection is cleared, but clients continue to progress.
@@ -81,12 +112,55 @@ impl Selection {
         }
     }
 
+    /// Begin selecting again from the endpoint that is farther away from `pos`, keeping the mode.
+    ///
+    /// Returns `false` if there is no completed selection to extend.
+    pub fn extend(&mut self, hit: PixelPoint, pos: CellPos, columns: usize) -> bool {
+        let Self::Selected {
+            mode,
+            from,
+            to,
+            resolved,
+        } = *self
+        else {
+            return false;
+        };
+
+        // The distance is measured in cells as if all rows were concatenated.
+        let linear = |p: CellPos| {
+            p.row
+                .saturating_mul(columns.cast_signed())
+                .saturating_add(p.column)
+        };
+        let distance = |p: CellPos| linear(p).abs_diff(linear(pos));
+
+        let anchor = if distance(from) < distance(to) {
+            to
+        } else {
+            from
+        };
+
+        *self = Self::Selecting {
+            mode,
+            from: anchor,
+            to: hit,
+            resolved,
+        };
+        true
+    }
+
     pub fn end(&mut self, to: CellPos) {
         *self = match &self {
-            Self::Selecting { mode, from, .. } => Self::Selected {
+            Self::Selecting {
+                mode,
+                from,
+                resolved,
+                ..
+            } => Self::Selected {
                 mode: *mode,
                 from: *from,
                 to,
+                resolved: *resolved,
             },
             _ => {
                 error!(
@@ -135,7 +209,17 @@ impl SelectedRange {
         }
     }
 
-    pub fn extend(self, mode: SelectionMode, terminal: &Terminal) -> Self {
+    /// Extend the range to the boundaries of the mode.
+    ///
+    /// In word mode, positions inside the `resolved` smart selection are extended to it instead of
+    /// the word around them. In zone mode, the range is extended to cover the `resolved` zone.
+    pub fn extend(
+        self,
+        mode: SelectionMode,
+        resolved: Option<SelectedRange>,
+        terminal: &Terminal,
+        config: &Config,
+    ) -> Self {
         match mode {
             SelectionMode::Cell => {
                 let range_a = cell_around(self.start, terminal);
@@ -143,15 +227,20 @@ impl SelectedRange {
                 Self::boundary(range_a, range_b)
             }
             SelectionMode::Word => {
-                let range_a = word_around(self.start, terminal);
-                let range_b = word_around(self.end, terminal);
-                Self::boundary(range_a, range_b)
+                let around = |pos| match resolved {
+                    Some(smart) if smart.contains(pos) => smart,
+                    _ => word_around(pos, terminal, config),
+                };
+                Self::boundary(around(self.start), around(self.end))
             }
             SelectionMode::Line => {
                 let range_a = line_around(self.start, terminal);
                 let range_b = line_around(self.end, terminal);
                 Self::boundary(range_a, range_b)
             }
+            // Detail: Extending would mix up the columns of the start and the end.
+            SelectionMode::Rectangular => self,
+            SelectionMode::Zone => resolved.map_or(self, |zone| Self::boundary(zone, self)),
         }
     }
 
@@ -163,6 +252,10 @@ impl SelectedRange {
         &self.end
     }
 
+    pub fn contains(&self, pos: CellPos) -> bool {
+        (self.start..=self.end).contains(&pos)
+    }
+
     pub fn stable_rows(&self) -> Range<StableRowIndex> {
         self.start.row..self.end.row.saturating_add(1)
     }
@@ -211,7 +304,15 @@ impl SelectedRange {
         }
     }
 
-    pub fn clamp_to_rows(self, rows: Range<StableRowIndex>, columns: usize) -> Option<Self> {
+    /// Clamp the selection to the rows.
+    ///
+    /// Rectangular selections keep their columns.
+    pub fn clamp_to_rows(
+        self,
+        rows: Range<StableRowIndex>,
+        columns: usize,
+        rectangular: bool,
+    ) -> Option<Self> {
         if !self.stable_rows().intersects(&rows) {
             return None;
         }
@@ -220,13 +321,17 @@ impl SelectedRange {
         let mut end = self.end;
         if rows.start > start.row {
             start.row = rows.start;
-            start.column = 0;
+            if !rectangular {
+                start.column = 0;
+            }
         }
         if rows.end <= end.row {
             end.row = rows.end - 1;
-            end.column = columns.cast_signed() - 1;
+            if !rectangular {
+                end.column = columns.cast_signed() - 1;
+            }
         }
-        if start.row == end.row && start.column > end.column {
+        if !rectangular && start.row == end.row && start.column > end.column {
             return None;
         }
         Some(Self::new(start, end))
@@ -257,27 +362,88 @@ pub fn cell_around(pos: CellPos, terminal: &Terminal) -> SelectedRange {
 // Mostly copied from wezterm-gui/src/selection.rs
 
 /// Computes the selection range for the word around the specified coords
-pub fn word_around(pos: CellPos, terminal: &Terminal) -> SelectedRange {
+pub fn word_around(pos: CellPos, terminal: &Terminal, config: &Config) -> SelectedRange {
     for logical in get_logical_lines(terminal, pos.row.with_len(1)) {
         if !logical.contains_y(pos.row) {
             continue;
         }
 
         let start_idx = logical.xy_to_logical_x(pos.column.max(0).cast_unsigned(), pos.row);
-        return match logical
-            .logical
-            .compute_double_click_range(start_idx, is_double_click_word)
-        {
-            DoubleClickRange::RangeWithWrap(click_range) | DoubleClickRange::Range(click_range) => {
-                click_range_to_selected_range(&logical, click_range)
+
+        let click_range = match config.word_selection {
+            WordSelection::Boundary => {
+                match logical.logical.compute_double_click_range(start_idx, |s| {
+                    is_double_click_word(s, &config.word_boundary)
+                }) {
+                    DoubleClickRange::RangeWithWrap(click_range)
+                    | DoubleClickRange::Range(click_range) => click_range,
+                }
             }
+            WordSelection::Unicode => unicode_word_range(&logical.logical, start_idx),
         };
+        return click_range_to_selected_range(&logical, click_range);
     }
 
     pos.into()
 }
 
-fn click_range_to_selected_range(
+/// Computes the selection range of the first smart selection rule's match around the specified
+/// coords.
+///
+/// Smart selection ranges take precedence over the words in them.
+pub fn smart_selection_around(
+    pos: CellPos,
+    terminal: &Terminal,
+    config: &Config,
+) -> Option<SelectedRange> {
+    let logical = get_logical_lines(terminal, pos.row.with_len(1))
+        .into_iter()
+        .find(|logical| logical.contains_y(pos.row))?;
+    let start_idx = logical.xy_to_logical_x(pos.column.max(0).cast_unsigned(), pos.row);
+    let click_range =
+        smart_selection_range(&logical.logical, start_idx, &config.smart_selection_rules)?;
+    Some(click_range_to_selected_range(&logical, click_range))
+}
+
+/// The cells of the first smart selection rule's match that covers the cell.
+fn smart_selection_range(
+    line: &Line,
+    cell_index: usize,
+    rules: &SmartSelectionRules,
+) -> Option<Range<usize>> {
+    let line_text = LineText::new(line);
+    rules.iter().find_map(|rule| {
+        rule.find_iter(&line_text.text)
+            // Robustness: Matching fails if a rule exceeds the backtrack limit.
+            .map_while(|found| {
+                found
+                    .inspect_err(|e| warn!("Smart selection rule `{}` failed: {e}", rule.as_str()))
+                    .ok()
+            })
+            .filter_map(|found| line_text.cell_range(found.range()))
+            .find(|cells| cells.contains(&cell_index))
+    })
+}
+
+/// The cells of the Unicode word segment (UAX #29) that covers the cell.
+///
+/// Detail: Whitespace and punctuation form their own segments.
+fn unicode_word_range(line: &Line, cell_index: usize) -> Range<usize> {
+    let line_text = LineText::new(line);
+    line_text
+        .byte_offset(cell_index)
+        .and_then(|byte| {
+            line_text
+                .text
+                .split_word_bound_indices()
+                .map(|(offset, segment)| offset.with_len(segment.len()))
+                .find(|bytes| bytes.contains(&byte))
+        })
+        .and_then(|bytes| line_text.cell_range(bytes))
+        .unwrap_or(cell_index.with_len(1))
+}
+
+pub fn click_range_to_selected_range(
     logical: &LogicalLine,
     click_range: Range<usize>,
 ) -> SelectedRange {
@@ -312,14 +478,35 @@ pub fn line_around(pos: CellPos, terminal: &Terminal) -> SelectedRange {
     pos.into()
 }
 
-fn is_double_click_word(s: &str) -> bool {
+/// Computes the selection range for the semantic zone around the specified coords.
+///
+/// Detail: Coords past the end of a line, which are not covered by any zone, select the zone
+/// before them.
+pub fn zone_around(pos: CellPos, terminal: &mut Terminal) -> SelectedRange {
+    let zones = match terminal.get_semantic_zones() {
+        Ok(zones) => zones,
+        Err(e) => {
+            warn!("Querying the semantic zones failed: {e:?}");
+            return pos.into();
+        }
+    };
+
+    zones
+        .iter()
+        .map(|zone| {
+            SelectedRange::new(
+                CellPos::new(zone.start_x.cast_signed(), zone.start_y),
+                CellPos::new(zone.end_x.cast_signed(), zone.end_y),
+            )
+        })
+        .rfind(|zone| *zone.start() <= pos)
+        .unwrap_or(pos.into())
+}
+
+fn is_double_click_word(s: &str, word_boundary: &str) -> bool {
     match s.chars().count() {
-        1 => !DEFAULT_WORD_BOUNDARY.contains(s),
+        1 => !word_boundary.contains(s),
         0 => false,
         _ => true,
     }
 }
-
-// Feature: Make this configurable
-// Precision: Use the help of `unicode_segmentation`?
-const DEFAULT_WORD_BOUNDARY: &str = " \t\n{[}]()\"'`";
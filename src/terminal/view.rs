@@ -6,7 +6,12 @@ use euclid::Point2D;
 use log::info;
 use rangeset::RangeSet;
 
-use termwiz::{cellcluster::CellCluster, color::ColorAttribute, surface::CursorShape};
+use termwiz::{
+    cell::unicode_column_width,
+    cellcluster::CellCluster,
+    color::{ColorAttribute, SrgbaTuple},
+    surface::{CursorShape, SEQ_ZERO},
+};
 use wezterm_term::{
     CellAttributes, Hyperlink, Intensity, Line, StableRowIndex, Underline, color::ColorPalette,
 };
@@ -23,21 +28,41 @@ use massive_shell::Scene;
 use super::TerminalGeometry;
 use crate::{
     TerminalFont,
+    config::Config,
     range_ops::{RangeOps, WithLength},
     terminal::{
-        SelectedRange, TerminalViewGeometry, cursor::CursorMetrics,
+        CellPos, LineSize, ScrollbarGeometry, ScrollbarTicks, SelectedRange, TerminalViewGeometry,
+        VisualOrder, contrast, cursor::CursorMetrics, html, images::ImageCache,
         scroll_locations::ScrollLocations,
     },
     view_geometry::CellRect,
 };
 
 const SCROLL_ANIMATION_DURATION: Duration = Duration::from_millis(100);
+/// The factor the selection background's alpha is scaled with when the terminal is not focused.
+const UNFOCUSED_SELECTION_ALPHA_SCALE: f32 = 0.5;
+// Feature: Make the search match colors configurable.
+const SEARCH_MATCH_COLOR: SrgbaTuple = SrgbaTuple(1.0, 0.85, 0.2, 0.35);
+const SEARCH_CURRENT_MATCH_COLOR: SrgbaTuple = SrgbaTuple(1.0, 0.5, 0.0, 0.6);
+// Feature: Make the copy mode cursor color configurable.
+const COPY_MODE_CURSOR_COLOR: SrgbaTuple = SrgbaTuple(0.3, 0.7, 1.0, 0.6);
+// Feature: Make the scrollbar colors configurable.
+const SCROLLBAR_TRACK_COLOR: SrgbaTuple = SrgbaTuple(0.5, 0.5, 0.5, 0.15);
+const SCROLLBAR_THUMB_COLOR: SrgbaTuple = SrgbaTuple(0.8, 0.8, 0.8, 0.6);
+const SCROLLBAR_SEARCH_MATCH_COLOR: SrgbaTuple = SrgbaTuple(1.0, 0.85, 0.2, 0.9);
+const SCROLLBAR_PROMPT_COLOR: SrgbaTuple = SrgbaTuple(0.3, 0.7, 1.0, 0.9);
+/// The height of the ticks of search matches and prompts on the scrollbar's track.
+const SCROLLBAR_TICK_HEIGHT_PX: f64 = 2.0;
+/// The scrollbar stays visible for this duration after scrolling stopped, and then fades out for
+/// the same duration.
+const SCROLLBAR_FADE_DURATION: Duration = Duration::from_secs(1);
 
 #[derive(Debug, Clone)]
 pub struct TerminalViewParams {
     pub fonts: FontManager,
     pub font: TerminalFont,
     pub location: Handle<Location>,
+    pub config: Arc<Config>,
 }
 
 /// TerminalView is the into a terminal's screen lines.
@@ -79,7 +104,26 @@ pub struct TerminalView {
     /// We use a VecDeque because we want this to be optimized for scrolling.
     lines: VecDeque<LineVisuals>,
     cursor: Option<Handle<Visual>>,
-    selection: Option<SelectionVisual>,
+    selection: Option<HighlightsVisual>,
+    search_matches: Option<HighlightsVisual>,
+    copy_mode_cursor: Option<HighlightsVisual>,
+    /// The text the input method is composing and where and how it's rendered.
+    preedit: Option<(String, CursorMetrics, Handle<Visual>)>,
+    /// The search status rendered over the bottom line and its top pixel position.
+    search_bar: Option<(String, i64, Handle<Visual>)>,
+
+    scrollbar: Option<Handle<Visual>>,
+    /// The opacity of the scrollbar, fully visible from 1 to 2.
+    ///
+    /// Detail: It animates linearly from 2 to 0, so that the scrollbar stays visible for the first
+    /// half of the animation and fades out in the second.
+    scrollbar_alpha: Animated<f64>,
+    /// The final scroll offset of the last update of the scrollbar.
+    scrollbar_scroll_offset_px: f64,
+    /// The ticks on the scrollbar's track and their colors, kept while the scrollbar is hidden.
+    scrollbar_ticks: Vec<(Rect, SrgbaTuple)>,
+
+    images: ImageCache,
 }
 
 #[derive(Debug)]
@@ -94,10 +138,17 @@ struct LineVisuals {
     ///
     /// Might be negative for lines over the top of the terminal's stable range.
     top_offset: i64,
+
+    /// The size the line was last rendered with.
+    size: LineSize,
+
+    /// The visual order the line was last rendered with, `None` if it matches the logical order.
+    visual_order: Option<Arc<VisualOrder>>,
 }
 
+/// A visual highlighting ranges of cells, like the selection.
 #[derive(Debug)]
-struct SelectionVisual {
+struct HighlightsVisual {
     row_range: Range<StableRowIndex>,
     visual: Handle<Visual>,
 }
@@ -135,6 +186,15 @@ impl TerminalView {
             lines: VecDeque::new(),
             cursor: None,
             selection: None,
+            search_matches: None,
+            copy_mode_cursor: None,
+            preedit: None,
+            search_bar: None,
+            scrollbar: None,
+            scrollbar_alpha: scene.animated(0.0),
+            scrollbar_scroll_offset_px: scroll_offset_px as f64,
+            scrollbar_ticks: Vec::new(),
+            images: ImageCache::default(),
         }
     }
 }
@@ -163,6 +223,10 @@ impl TerminalView {
         );
     }
 
+    pub fn is_scrollbar_visible(&self) -> bool {
+        self.scrollbar.is_some()
+    }
+
     pub fn final_scroll_offset_px(&self) -> f64 {
         self.scroll_offset_px.final_value()
     }
@@ -232,10 +296,27 @@ impl TerminalView {
         let stable_range = topmost_stable_render_line as StableRowIndex
             ..(bottom_stable_render_line + 1) as StableRowIndex;
 
+        let line_sizes = (self.first_line_stable_index..)
+            .zip(&self.lines)
+            .filter(|(_, line)| line.size != LineSize::Single)
+            .map(|(stable_index, line)| (stable_index, line.size))
+            .collect();
+
+        let visual_orders = (self.first_line_stable_index..)
+            .zip(&self.lines)
+            .filter_map(|(stable_index, line)| {
+                line.visual_order
+                    .as_ref()
+                    .map(|order| (stable_index, order.clone()))
+            })
+            .collect();
+
         TerminalViewGeometry {
             terminal: *terminal_geometry,
             stable_range_ascend_px: topmost_stable_render_line_ascend as u32,
             stable_range,
+            line_sizes,
+            visual_orders,
         }
     }
 }
@@ -266,10 +347,17 @@ impl TerminalView {
         // Because the cursor does not leave the visible part (I hope), we ignore that for now
         // because its matrix can be recreated any time.
         let mut visuals_range = self.first_line_stable_index.with_len(self.lines.len());
-        if let Some(selection) = &self.selection {
+        for highlights in [
+            &self.selection,
+            &self.search_matches,
+            &self.copy_mode_cursor,
+        ]
+        .into_iter()
+        .flatten()
+        {
             // Review: Unioning the selection can have a nasty large range extension, which needs
             // many locations active.
-            visuals_range = visuals_range.union(selection.row_range.clone());
+            visuals_range = visuals_range.union(highlights.row_range.clone());
         }
         self.locations.mark_used(visuals_range);
     }
@@ -294,11 +382,14 @@ impl ViewUpdate<'_> {
         first_line_stable_index: StableRowIndex,
         lines: &[Line],
         underlined_hyperlink: Option<&Arc<Hyperlink>>,
+        selection: Option<&SelectedRange>,
+        rectangular_selection: bool,
     ) -> Result<()> {
         self.view.update_lines(
             first_line_stable_index,
             lines,
             underlined_hyperlink,
+            selection.map(|s| (s, rectangular_selection)),
             self.reverse_video,
         )
     }
@@ -307,13 +398,74 @@ impl ViewUpdate<'_> {
         self.view.update_cursor(self.scene, metrics);
     }
 
+    /// Render the text the input method is composing at the cursor.
+    pub fn preedit(&mut self, preedit: Option<(&str, &CursorMetrics)>) -> Result<()> {
+        self.view.update_preedit(self.scene, preedit)
+    }
+
     pub fn selection(
         &mut self,
         selection: Option<SelectedRange>,
+        rectangular: bool,
+        terminal_view_geometry: &TerminalViewGeometry,
+        focused: bool,
+    ) {
+        self.view.update_selection(
+            self.scene,
+            selection,
+            rectangular,
+            terminal_view_geometry,
+            focused,
+        );
+    }
+
+    /// Highlight the search matches.
+    ///
+    /// `matches` should contain the visible matches only.
+    pub fn search_matches(
+        &mut self,
+        matches: &[SelectedRange],
+        current: Option<SelectedRange>,
+        terminal_view_geometry: &TerminalViewGeometry,
+    ) {
+        self.view
+            .update_search_matches(self.scene, matches, current, terminal_view_geometry);
+    }
+
+    /// Render the search status over the bottom line, `None` if not searching.
+    pub fn search_bar(
+        &mut self,
+        status: Option<&str>,
+        terminal_view_geometry: &TerminalViewGeometry,
+    ) -> Result<()> {
+        self.view
+            .update_search_bar(self.scene, status, terminal_view_geometry)
+    }
+
+    pub fn copy_mode_cursor(
+        &mut self,
+        cursor: Option<CellPos>,
         terminal_view_geometry: &TerminalViewGeometry,
     ) {
         self.view
-            .update_selection(self.scene, selection, terminal_view_geometry);
+            .update_copy_mode_cursor(self.scene, cursor, terminal_view_geometry);
+    }
+
+    /// Show the scrollbar while the user scrolls or while `active`, for example while its thumb
+    /// is dragged.
+    ///
+    /// Scrolling does not show the scrollbar while `following_output`. While the scrollbar is
+    /// visible, `ticks` is called and the rows it returns are marked on the track. If it returns
+    /// `None`, the previous ticks are kept.
+    pub fn scrollbar(
+        &mut self,
+        scrollbar: Option<&ScrollbarGeometry>,
+        active: bool,
+        following_output: bool,
+        ticks: impl FnOnce() -> Option<ScrollbarTicks>,
+    ) {
+        self.view
+            .update_scrollbar(self.scene, scrollbar, active, following_output, ticks);
     }
 }
 
@@ -347,6 +499,8 @@ impl TerminalView {
                 text,
                 overlays,
                 top_offset,
+                size: LineSize::Single,
+                visual_order: None,
             }
         };
 
@@ -367,8 +521,16 @@ impl TerminalView {
                 let to_remove = top_delta;
                 self.lines.drain(0..(to_remove as usize));
                 self.first_line_stable_index += to_remove;
+                // A bottom half that lost its top half renders both halves now.
+                if self.lines.front().map(|l| l.size) == Some(LineSize::DoubleHeightBottom) {
+                    required_line_updates.add(view_range.start);
+                }
             }
             _ if top_delta < 0 => {
+                // A bottom half that gets its top half back renders only its own half.
+                if self.lines.front().map(|l| l.size) == Some(LineSize::DoubleHeightBottom) {
+                    required_line_updates.add(current_range.start);
+                }
                 let range = view_range.start..current_range.start;
                 range.rev().for_each(|stable_index| {
                     self.lines.push_front(new_visual(stable_index));
@@ -414,6 +576,7 @@ impl TerminalView {
         first_line_stable_index: StableRowIndex,
         lines: &[Line],
         underlined_hyperlink: Option<&Arc<Hyperlink>>,
+        selection: Option<(&SelectedRange, bool)>,
         reverse_video: bool,
     ) -> Result<()> {
         let update_range = first_line_stable_index.with_len(lines.len());
@@ -425,9 +588,30 @@ impl TerminalView {
         for (i, line) in lines.iter().enumerate() {
             let line_index =
                 (update_range.start - self.first_line_stable_index + i as isize) as usize;
+            // Detail: The top half of the first line is above the view.
+            let bottom_half_alone =
+                line_index == 0 && LineSize::from_line(line) == LineSize::DoubleHeightBottom;
 
             let top = self.lines[line_index].top_offset;
-            let (shapes, overlay_shapes) = {
+            let selected_columns = selection
+                .map(|(s, rectangular)| {
+                    s.cols_for_row(update_range.start + i as isize, rectangular)
+                })
+                .unwrap_or_default();
+            let visual_order = self
+                .params
+                .config
+                .bidi_hint()
+                .and_then(|hint| VisualOrder::from_line(line, hint))
+                .map(Arc::new);
+            let images = self.images.line_images(
+                line,
+                visual_order.as_deref(),
+                self.font().cell_size_px(),
+                top,
+            );
+
+            let (mut shapes, mut overlay_shapes) = {
                 // Lock the font_system for the least amount of time possible. This is shared with
                 // the renderer.
                 let mut font_system = self.params.fonts.lock();
@@ -435,12 +619,25 @@ impl TerminalView {
                     &mut font_system,
                     top,
                     line,
+                    bottom_half_alone,
+                    visual_order.as_deref(),
                     underlined_hyperlink,
+                    selected_columns,
                     reverse_video,
                 )?
             };
 
+            // Images with a negative z-index are rendered with the text, all others with the
+            // overlays, which are placed above the text.
+            //
+            // Precision: Inside the text visual, the order of images and glyph runs depends on the
+            // renderer.
+            shapes.splice(0..0, images.below_text);
+            overlay_shapes.extend(images.above_text);
+
             let line_visuals = &mut self.lines[line_index];
+            line_visuals.size = LineSize::from_line(line);
+            line_visuals.visual_order = visual_order;
 
             line_visuals.text.update_with(|v| {
                 v.shapes = shapes.into();
@@ -454,35 +651,79 @@ impl TerminalView {
         Ok(())
     }
 
+    /// Create the shapes of a line and the shapes of its overlays.
+    ///
+    /// `bottom_half_alone` is set for the bottom half of a double height line if its top half is
+    /// not rendered.
+    #[allow(clippy::too_many_arguments)]
     fn create_line_shapes(
         &self,
         font_system: &mut FontSystem,
         top: i64,
         line: &Line,
+        bottom_half_alone: bool,
+        visual_order: Option<&VisualOrder>,
         active_hyperlink: Option<&Arc<Hyperlink>>,
+        selected_columns: Range<usize>,
         reverse_video: bool,
     ) -> Result<(Vec<Shape>, Vec<Shape>)> {
-        // Production: Add bidi support
-        let clusters = line.cluster(None);
+        // Detail: With bidi enabled, clusters are split at direction changes, so every cluster is
+        // visually contiguous.
+        let clusters = line.cluster(self.params.config.bidi_hint());
+        let line_size = LineSize::from_line(line);
 
         // Performance: Background shapes are not included in the capacity. Use a temporary array here.
         let mut shapes: Vec<Shape> = Vec::with_capacity(clusters.len());
         // Performance: Can we use some capacity or a temporary array here?
         let mut overlay_shapes = Vec::new();
-        let mut left = 0;
-        let cell_size_px = self.font().cell_size_px().width as i64;
+        let cell_size_px = self.font().cell_size_px().width as i64 * line_size.width_scale() as i64;
+        let selection_foreground = self.selection_foreground();
+
+        // The top half of a double height line renders the glyphs and decorations of both halves.
+        // Without its top half, the bottom half renders them as if it were the top half.
+        //
+        // Precision: Glyphs are not clipped to their line. A bottom half that renders both halves
+        // renders the upper half of its glyphs above the view.
+        let (glyph_line_size, glyph_top) = if bottom_half_alone {
+            let line_height = self.font().cell_size_px().height as i64;
+            (LineSize::DoubleHeightTop, top - line_height)
+        } else {
+            (line_size, top)
+        };
 
         // Optimization: Combine clusters with compatible attributes. Colors and widths can vary
         // inside a GlyphRun.
         for cluster in clusters {
-            let attributes =
-                AttributeResolver::new(&self.color_palette, reverse_video, &cluster.attrs);
+            let attributes = AttributeResolver::new(
+                &self.color_palette,
+                &self.params.config,
+                reverse_video,
+                &cluster.attrs,
+            );
 
-            let run =
-                Self::cluster_to_run(font_system, self.font(), &attributes, (left, top), &cluster);
+            let left_column = Self::cluster_visual_start(&cluster, visual_order);
+            let left = left_column as i64 * cell_size_px;
+
+            let runs = (glyph_line_size != LineSize::DoubleHeightBottom).then(|| {
+                Self::cluster_to_runs(
+                    font_system,
+                    self.font(),
+                    &attributes,
+                    (left, glyph_top),
+                    &cluster,
+                    glyph_line_size,
+                    visual_order,
+                    (&selected_columns, selection_foreground),
+                )
+            });
 
-            let background =
-                Self::cluster_background(&cluster, self.font(), &attributes, (left, top));
+            let background = Self::cluster_background(
+                &cluster,
+                self.font(),
+                &attributes,
+                (left, top),
+                line_size,
+            );
 
             let underline_hyperlink =
                 active_hyperlink.is_some() && cluster.attrs.hyperlink() == active_hyperlink;
@@ -491,11 +732,17 @@ impl TerminalView {
                 &cluster,
                 self.font(),
                 &attributes,
-                (left, top),
+                (left, glyph_top),
+                glyph_line_size,
                 underline_hyperlink,
             );
 
-            shapes.push(run.into());
+            if let Some((run, selected_run)) = runs {
+                shapes.push(run.into());
+                if let Some(selected_run) = selected_run {
+                    shapes.push(selected_run.into());
+                }
+            }
 
             if let Some(background) = background {
                 shapes.push(background)
@@ -504,21 +751,48 @@ impl TerminalView {
             if let Some(overlay) = overlay {
                 overlay_shapes.push(overlay);
             }
-
-            left += cluster.width as i64 * cell_size_px;
         }
 
         Ok((shapes, overlay_shapes))
     }
 
-    fn cluster_to_run(
+    /// The leftmost visual column of a cluster.
+    fn cluster_visual_start(cluster: &CellCluster, visual_order: Option<&VisualOrder>) -> usize {
+        match visual_order {
+            Some(visual_order) => {
+                visual_order.visual_start(cluster.first_cell_idx.with_len(cluster.width))
+            }
+            None => cluster.first_cell_idx,
+        }
+    }
+
+    /// Shape the cluster and return its glyph run and a second glyph run for the glyphs inside the
+    /// selected columns, if there are any.
+    ///
+    /// Precision: Ligatures crossing the selection boundary are colored by their first cell.
+    #[allow(clippy::too_many_arguments)]
+    fn cluster_to_runs(
         font_system: &mut FontSystem,
         font: &TerminalFont,
         attributes: &AttributeResolver,
         (left, top): (i64, i64),
         cluster: &CellCluster,
-    ) -> GlyphRun {
+        line_size: LineSize,
+        visual_order: Option<&VisualOrder>,
+        (selected_columns, selection_foreground): (&Range<usize>, Color),
+    ) -> (GlyphRun, Option<GlyphRun>) {
         let text_weight = attributes.text_weight();
+        let width_scale = line_size.width_scale() as u32;
+        // Double height lines are rendered with glyphs at twice the font size. Double width lines
+        // keep the glyphs and center them inside their cells.
+        //
+        // Precision: GlyphKey only supports uniform sizes, so glyphs can't be stretched
+        // horizontally.
+        let (glyph_scale, glyph_offset_x) = if line_size.is_double_height() {
+            (2, 0)
+        } else {
+            (1, (width_scale - 1) * font.glyph_advance_px / 2)
+        };
         let font_weight = fontdb::Weight(text_weight.0);
 
         // Performance: BufferLine makes a copy of the text, is there a better way?
@@ -548,12 +822,17 @@ impl TerminalView {
             .flat_map(|word| &word.glyphs);
 
         let mut glyphs = Vec::with_capacity(cluster.width);
+        let mut selected_glyphs = Vec::new();
+        let left_column = Self::cluster_visual_start(cluster, visual_order);
 
         for glyph in shaped_glyphs {
             // We place the glyphs based on what the cluster says not what the layout engine
             // provides.
-            let cell_index = cluster.byte_to_cell_idx(glyph.start) - cluster.first_cell_idx;
-            let glyph_x = cell_index as u32 * font.glyph_advance_px;
+            let cell_index = cluster.byte_to_cell_idx(glyph.start);
+            let visual_column = visual_order.map_or(cell_index, |o| o.visual_column(cell_index));
+            let glyph_x =
+                (visual_column - left_column) as u32 * font.glyph_advance_px * width_scale
+                    + glyph_offset_x;
 
             // Optimization: Don't pass empty / blank glyphs.
 
@@ -563,27 +842,51 @@ impl TerminalView {
                 GlyphKey::new(
                     glyph.font_id,
                     glyph.glyph_id,
-                    font.size,
+                    font.size * glyph_scale as f32,
                     TextWeight(glyph.font_weight.0),
                 ),
             );
-            glyphs.push(glyph);
+            if selected_columns.contains(&cell_index) {
+                selected_glyphs.push(glyph);
+            } else {
+                glyphs.push(glyph);
+            }
         }
 
-        GlyphRun {
+        let run = |text_color, glyphs| GlyphRun {
             translation: (left as _, top as _, 0.).into(),
             metrics: GlyphRunMetrics {
                 // Precision: compute this once for the font size so that it also matches the pixel cell
                 // size.
-                max_ascent: font.ascender_px,
-                max_descent: font.descender_px,
-                width: (cluster.width as u32 * font.glyph_advance_px),
+                max_ascent: font.ascender_px * glyph_scale,
+                max_descent: font.descender_px * glyph_scale,
+                width: (cluster.width as u32 * font.glyph_advance_px * width_scale),
             },
-            text_color: attributes.foreground_color,
+            text_color,
             // This looks redundant here.
             text_weight,
             glyphs,
-        }
+        };
+
+        let selected_run =
+            (!selected_glyphs.is_empty()).then(|| run(selection_foreground, selected_glyphs));
+
+        let foreground = color::from_srgba(attributes.foreground);
+        (run(foreground, glyphs), selected_run)
+    }
+
+    /// The color selected text is rendered with.
+    ///
+    /// Detail: A transparent `selection_fg` keeps the text readable by using the palette's
+    /// foreground color.
+    fn selection_foreground(&self) -> Color {
+        let palette = &self.color_palette;
+        let SrgbaTuple(.., alpha) = palette.selection_fg;
+        color::from_srgba(if alpha > 0. {
+            palette.selection_fg
+        } else {
+            palette.foreground
+        })
     }
 
     /// Generates the background shape for the cluster.
@@ -592,15 +895,17 @@ impl TerminalView {
         font: &TerminalFont,
         attributes: &AttributeResolver,
         (left, top): (i64, i64),
+        line_size: LineSize,
     ) -> Option<Shape> {
-        let Some(background_color) = attributes.background_color else {
+        let Some(background_color) = attributes.background.map(color::from_srgba) else {
             // Assume that the background is rendered in the default background color.
             return None;
         };
 
         let size: Size = (
             // Precision: We keep multiplication in the u32 range here. Unlikely it's overflowing.
-            (cluster.width as u32 * font.cell_size_px().width) as f64,
+            (cluster.width as u32 * font.cell_size_px().width * line_size.width_scale() as u32)
+                as f64,
             font.cell_size_px().height as f64,
         )
             .into();
@@ -618,8 +923,15 @@ impl TerminalView {
         font: &TerminalFont,
         attributes: &AttributeResolver,
         (left, top): (i64, i64),
+        line_size: LineSize,
         underline_hyperlink: bool,
     ) -> Option<Shape> {
+        // Like the glyphs, decorations of double height lines are rendered by the top half.
+        if line_size == LineSize::DoubleHeightBottom {
+            return None;
+        }
+        let vertical_scale = if line_size.is_double_height() { 2 } else { 1 };
+
         let underline = cluster.attrs.underline();
         // Feature: Don't highlight if the hyperlink is not hovered over.
         let effective_underline = match (underline_hyperlink, underline) {
@@ -644,14 +956,15 @@ impl TerminalView {
         if let Some(underline_metrics) = underline_metrics {
             let lt: Point = (
                 left as f64,
-                (top + underline_metrics.position as i64) as f64,
+                (top + (underline_metrics.position * vertical_scale) as i64) as f64,
             )
                 .into();
 
             let size: Size = (
                 // Precision: We keep multiplication in the u32 range here. Unlikely it's overflowing.
-                (cluster.width as u32 * font.cell_size_px().width) as f64,
-                underline_metrics.thickness as f64,
+                (cluster.width as u32 * font.cell_size_px().width * line_size.width_scale() as u32)
+                    as f64,
+                (underline_metrics.thickness * vertical_scale) as f64,
             )
                 .into();
 
@@ -664,6 +977,66 @@ impl TerminalView {
     }
 }
 
+// HTML
+
+impl TerminalView {
+    /// Append the cells of a line's columns as HTML spans.
+    ///
+    /// Spans use the resolved colors of the cells, including reverse video and the minimum
+    /// contrast. Trailing whitespace is dropped if `trim_end` is set.
+    pub fn cells_to_html(
+        &self,
+        line: &Line,
+        columns: Range<usize>,
+        trim_end: bool,
+        reverse_video: bool,
+        html: &mut String,
+    ) {
+        let mut cells: Vec<_> = line
+            .visible_cells()
+            .filter(|cell| columns.contains(&cell.cell_index()))
+            .collect();
+        if trim_end {
+            let non_blank = cells
+                .iter()
+                .rposition(|cell| !cell.str().trim().is_empty())
+                .map_or(0, |i| i + 1);
+            cells.truncate(non_blank);
+        }
+
+        let mut span: Option<(html::CellStyle, String)> = None;
+        for cell in cells {
+            let style = AttributeResolver::new(
+                &self.color_palette,
+                &self.params.config,
+                reverse_video,
+                cell.attrs(),
+            )
+            .html_style();
+            match &mut span {
+                Some((current, text)) if *current == style => text.push_str(cell.str()),
+                _ => {
+                    if let Some((style, text)) = span.replace((style, cell.str().to_string())) {
+                        html::push_span(html, &style, &text);
+                    }
+                }
+            }
+        }
+        if let Some((style, text)) = span {
+            html::push_span(html, &style, &text);
+        }
+    }
+
+    /// Wrap HTML spans in a document that renders them on the terminal's default colors.
+    pub fn html_document(&self, body: &str) -> String {
+        html::document(
+            self.color_palette.foreground,
+            self.color_palette.background,
+            body,
+        )
+    }
+}
+
 // Cursor
 
 #[derive(Debug)]
@@ -682,11 +1055,126 @@ impl TerminalView {
             let (location, top_px) = self
                 .locations
                 .acquire_line_location(scene, metrics.stable_y);
-            let shape = self.cursor_shape(shape_type, metrics.pos.x, metrics.width, top_px);
+            let width_scale = metrics.line_size.width_scale();
+            let shape = self.cursor_shape(
+                shape_type,
+                metrics.visual_x * width_scale,
+                metrics.width * width_scale,
+                top_px,
+            );
             [shape].at(location).enter(scene)
         })
     }
 
+    fn update_preedit(
+        &mut self,
+        scene: &Scene,
+        preedit: Option<(&str, &CursorMetrics)>,
+    ) -> Result<()> {
+        let Some((text, metrics)) = preedit else {
+            self.preedit = None;
+            return Ok(());
+        };
+        if let Some((rendered_text, rendered_metrics, _)) = &self.preedit
+            && rendered_text == text
+            && rendered_metrics.stable_y == metrics.stable_y
+            && rendered_metrics.visual_x == metrics.visual_x
+            && rendered_metrics.line_size == metrics.line_size
+        {
+            return Ok(());
+        }
+
+        // The preedit text is underlined and covers the cells it's rendered on.
+        let mut attributes = CellAttributes::default();
+        attributes.set_underline(Underline::Single).set_background(
+            ColorAttribute::TrueColorWithDefaultFallback(self.color_palette.background),
+        );
+        // Precision: The preedit is rendered in logical order and does not wrap.
+        let mut line = Line::with_width(metrics.visual_x, SEQ_ZERO);
+        line.append_line(Line::from_text(text, &attributes, SEQ_ZERO, None), SEQ_ZERO);
+
+        // Detail: The bottom half of a double height line renders nothing, its text is rendered
+        // by the top half. So the preedit is rendered as the top half on the line above.
+        let (line_size, stable_y) = match metrics.line_size {
+            LineSize::DoubleHeightBottom => (LineSize::DoubleHeightTop, metrics.stable_y - 1),
+            line_size => (line_size, metrics.stable_y),
+        };
+        line_size.apply_to(&mut line, SEQ_ZERO);
+
+        let (location, top_px) = self.locations.acquire_line_location(scene, stable_y);
+        let (mut shapes, overlay_shapes) = {
+            let mut font_system = self.params.fonts.lock();
+            self.create_line_shapes(
+                &mut font_system,
+                top_px,
+                &line,
+                false,
+                None,
+                None,
+                0..0,
+                false,
+            )?
+        };
+        shapes.extend(overlay_shapes);
+
+        let visual = shapes.at(location).with_decal_order(2).enter(scene);
+        self.preedit = Some((text.to_string(), metrics.clone(), visual));
+        Ok(())
+    }
+
+    fn update_search_bar(
+        &mut self,
+        scene: &Scene,
+        status: Option<&str>,
+        terminal_view_geometry: &TerminalViewGeometry,
+    ) -> Result<()> {
+        let Some(status) = status else {
+            self.search_bar = None;
+            return Ok(());
+        };
+
+        // The bar covers the full width of the view.
+        let columns = terminal_view_geometry.terminal_size().width;
+        let padding = columns.saturating_sub(unicode_column_width(status, None));
+        let text = format!("{status}{}", " ".repeat(padding));
+        let top_px = (terminal_view_geometry.vertical_pixel_span().end
+            - terminal_view_geometry.line_height_px()) as i64;
+        if let Some((rendered_text, rendered_top_px, _)) = &self.search_bar
+            && *rendered_text == text
+            && *rendered_top_px == top_px
+        {
+            return Ok(());
+        }
+
+        let mut attributes = CellAttributes::default();
+        attributes.set_reverse(true);
+        // Precision: A status wider than the view is not truncated.
+        let line = Line::from_text(&text, &attributes, SEQ_ZERO, None);
+        let (mut shapes, overlay_shapes) = {
+            let mut font_system = self.params.fonts.lock();
+            self.create_line_shapes(
+                &mut font_system,
+                top_px,
+                &line,
+                false,
+                None,
+                None,
+                0..0,
+                false,
+            )?
+        };
+        shapes.extend(overlay_shapes);
+
+        // Detail: Like the scrollbar, the bar does not scroll with the lines and is rendered above
+        // them.
+        let visual = shapes
+            .at(&self.params.location)
+            .with_decal_order(3)
+            .enter(scene);
+        self.search_bar = Some((text, top_px, visual));
+        Ok(())
+    }
+
     fn cursor_shape_type(shape: CursorShape, focused: bool) -> CursorShapeType {
         if !focused {
             return CursorShapeType::Rect;
@@ -761,69 +1249,264 @@ impl TerminalView {
         &mut self,
         scene: &Scene,
         selection: Option<SelectedRange>,
+        rectangular: bool,
+        terminal_view_geometry: &TerminalViewGeometry,
+        focused: bool,
+    ) {
+        let selection_color = color::from_srgba(self.selection_background(focused));
+        let highlights = self.highlights_visual(
+            scene,
+            selection.map(|range| (range, selection_color)),
+            rectangular,
+            terminal_view_geometry,
+        );
+        Self::update_highlights(&mut self.selection, scene, highlights);
+    }
+
+    fn update_search_matches(
+        &mut self,
+        scene: &Scene,
+        matches: &[SelectedRange],
+        current: Option<SelectedRange>,
         terminal_view_geometry: &TerminalViewGeometry,
     ) {
+        let highlights = self.highlights_visual(
+            scene,
+            matches.iter().map(|range| {
+                let color = if Some(*range) == current {
+                    SEARCH_CURRENT_MATCH_COLOR
+                } else {
+                    SEARCH_MATCH_COLOR
+                };
+                (*range, color::from_srgba(color))
+            }),
+            false,
+            terminal_view_geometry,
+        );
+        Self::update_highlights(&mut self.search_matches, scene, highlights);
+    }
+
+    /// The copy mode cursor is rendered like a selected cell in its own color.
+    fn update_copy_mode_cursor(
+        &mut self,
+        scene: &Scene,
+        cursor: Option<CellPos>,
+        terminal_view_geometry: &TerminalViewGeometry,
+    ) {
+        let highlights = self.highlights_visual(
+            scene,
+            cursor.map(|pos| (pos.into(), color::from_srgba(COPY_MODE_CURSOR_COLOR))),
+            false,
+            terminal_view_geometry,
+        );
+        Self::update_highlights(&mut self.copy_mode_cursor, scene, highlights);
+    }
+
+    fn update_scrollbar(
+        &mut self,
+        scene: &Scene,
+        scrollbar: Option<&ScrollbarGeometry>,
+        active: bool,
+        following_output: bool,
+        ticks: impl FnOnce() -> Option<ScrollbarTicks>,
+    ) {
+        let Some(scrollbar) = scrollbar else {
+            self.scrollbar = None;
+            return;
+        };
+
+        let final_scroll_offset_px = self.final_scroll_offset_px();
+        let scrolled = final_scroll_offset_px != self.scrollbar_scroll_offset_px;
+        self.scrollbar_scroll_offset_px = final_scroll_offset_px;
+        if active || (scrolled && !following_output) {
+            self.scrollbar_alpha
+                .animate(2.0, Duration::ZERO, Interpolation::Linear);
+            self.scrollbar_alpha
+                .animate(0.0, SCROLLBAR_FADE_DURATION * 2, Interpolation::Linear);
+        }
+
+        let alpha = self.scrollbar_alpha.value().clamp(0.0, 1.0) as f32;
+        if alpha == 0.0 {
+            self.scrollbar = None;
+            return;
+        }
+        let faded =
+            |SrgbaTuple(r, g, b, a): SrgbaTuple| color::from_srgba(SrgbaTuple(r, g, b, a * alpha));
+
+        if let Some(ticks) = ticks() {
+            self.scrollbar_ticks = Self::scrollbar_tick_rects(scrollbar, &ticks);
+        }
+
+        let track = scrollbar.track();
+        let mut shapes: Vec<Shape> = vec![
+            massive_shapes::Rect::new(track, faded(SCROLLBAR_TRACK_COLOR)).into(),
+            massive_shapes::Rect::new(
+                scrollbar.thumb(self.current_scroll_offset_px()),
+                faded(SCROLLBAR_THUMB_COLOR),
+            )
+            .into(),
+        ];
+        shapes.extend(
+            self.scrollbar_ticks.iter().map(|(rect, tick_color)| {
+                massive_shapes::Rect::new(*rect, faded(*tick_color)).into()
+            }),
+        );
+
+        match &mut self.scrollbar {
+            Some(visual) => visual.update_with(|v| v.shapes = shapes.into()),
+            None => {
+                // Detail: The scrollbar is rendered above everything else, including the composed
+                // text.
+                self.scrollbar = Some(
+                    shapes
+                        .at(&self.params.location)
+                        .with_decal_order(3)
+                        .enter(scene),
+                );
+            }
+        }
+    }
+
+    /// The rectangles of the ticks on the track and their colors.
+    ///
+    /// Performance: Ticks of rows that map to the same pixel are rendered once.
+    fn scrollbar_tick_rects(
+        scrollbar: &ScrollbarGeometry,
+        ticks: &ScrollbarTicks,
+    ) -> Vec<(Rect, SrgbaTuple)> {
+        let track = scrollbar.track();
+        let mut rects = Vec::new();
+        for (rows, tick_color) in [
+            (&ticks.search_match_rows, SCROLLBAR_SEARCH_MATCH_COLOR),
+            (&ticks.prompt_rows, SCROLLBAR_PROMPT_COLOR),
+        ] {
+            let mut last_y = None;
+            for row in rows {
+                let rect = Rect::new(
+                    Point::new(track.min_x(), scrollbar.row_y(*row)),
+                    Size::new(track.width(), SCROLLBAR_TICK_HEIGHT_PX),
+                );
+                let y = rect.min_y().round();
+                if last_y != Some(y) {
+                    rects.push((rect, tick_color));
+                    last_y = Some(y);
+                }
+            }
+        }
+        rects
+    }
+
+    fn update_highlights(
+        highlights: &mut Option<HighlightsVisual>,
+        scene: &Scene,
+        update: Option<(Range<StableRowIndex>, Visual)>,
+    ) {
+        let Some((row_range, visual)) = update else {
+            *highlights = None;
+            return;
+        };
+
+        match highlights {
+            Some(highlights) => {
+                highlights.row_range = row_range;
+                highlights.visual.update_if_changed(visual);
+            }
+            None => {
+                *highlights = Some(HighlightsVisual {
+                    row_range,
+                    visual: scene.stage(visual),
+                })
+            }
+        }
+    }
+
+    /// Create the visual that highlights the cell ranges with their colors.
+    ///
+    /// Returns the rows the visual covers and the visual, `None` if none of the ranges are visible.
+    fn highlights_visual(
+        &mut self,
+        scene: &Scene,
+        ranges: impl IntoIterator<Item = (SelectedRange, Color)>,
+        rectangular: bool,
+        terminal_view_geometry: &TerminalViewGeometry,
+    ) -> Option<(Range<StableRowIndex>, Visual)> {
         let terminal_geometry = &terminal_view_geometry.terminal;
         let columns = terminal_view_geometry.terminal.columns();
 
+        // Detail: Clip the ranges to what's actually visible. This keeps the number of matrices in
+        // use in check and also prevents outside rendering.
+        //
         // Architecture: Clipping should be done in the final selection rects. The SelectionRange
         // does not match the domain, but since we do have clamp_to_rows here, it makes everything
         // easier.
-        let selection = selection
-            .and_then(|sr| sr.clamp_to_rows(terminal_view_geometry.stable_range.clone(), columns));
-
-        match selection {
-            Some(selection_range) => {
-                // Detail: Clip the selection range to what's actually visible. This keeps the
-                // number of matrices in use in check and also prevents outside rendering.
+        let ranges: Vec<_> = ranges
+            .into_iter()
+            .filter_map(|(range, color)| {
+                range
+                    .clamp_to_rows(
+                        terminal_view_geometry.stable_range.clone(),
+                        columns,
+                        rectangular,
+                    )
+                    .map(|range| (range, color))
+            })
+            .collect();
+
+        let row_range = ranges
+            .iter()
+            .map(|(range, _)| range.stable_rows())
+            .reduce(RangeOps::union)?;
 
-                let rects_stable = Self::selection_rects(&selection_range, columns);
-                let cell_size = terminal_geometry.cell_size_px.cast::<f64>();
-                let location_stable_index = selection_range.stable_rows().start;
+        let cell_size = terminal_geometry.cell_size_px.cast::<f64>();
+        let location_stable_index = row_range.start;
 
-                let (location, top_px) = self
-                    .locations
-                    .acquire_line_location(scene, location_stable_index);
+        let (location, top_px) = self
+            .locations
+            .acquire_line_location(scene, location_stable_index);
 
-                let top_stable_px = location_stable_index as i64 * self.line_height_px() as i64;
-                let translation_offset = top_px - top_stable_px;
+        let top_stable_px = location_stable_index as i64 * self.line_height_px() as i64;
+        let translation_offset = top_px - top_stable_px;
 
-                let rects_final = rects_stable.iter().map(|r| {
-                    r.to_f64()
+        let shapes: Vec<Shape> = ranges
+            .iter()
+            .flat_map(|(range, color)| {
+                let rects_stable = Self::layout_selection_rects(
+                    Self::selection_rects(range, columns, rectangular),
+                    terminal_view_geometry,
+                    columns,
+                );
+                rects_stable.into_iter().map(move |r| {
+                    let rect = r
+                        .to_f64()
                         .scale(cell_size.width, cell_size.height)
                         .cast_unit::<PixelUnit>()
-                        .translate((0., translation_offset as f64).into())
-                });
-
-                let selection_color = color::from_srgba(self.color_palette.selection_bg);
-
-                let shapes: Vec<_> = rects_final
-                    .map(|r| massive_shapes::Rect::new(r, selection_color).into())
-                    .collect();
+                        .translate((0., translation_offset as f64).into());
+                    massive_shapes::Rect::new(rect, *color).into()
+                })
+            })
+            .collect();
 
-                let visual = Visual::new(location, shapes);
+        Some((row_range, Visual::new(location, shapes)))
+    }
 
-                //
-                match &mut self.selection {
-                    Some(selection) => {
-                        selection.row_range = selection_range.stable_rows();
-                        selection.visual.update_if_changed(visual);
-                    }
-                    None => {
-                        self.selection = Some(SelectionVisual {
-                            row_range: selection_range.stable_rows(),
-                            visual: scene.stage(visual),
-                        })
-                    }
-                }
-            }
-            None => self.selection = None,
+    /// The selection is rendered dimmer if the terminal is not focused.
+    fn selection_background(&self, focused: bool) -> SrgbaTuple {
+        let SrgbaTuple(r, g, b, alpha) = self.color_palette.selection_bg;
+        if focused {
+            SrgbaTuple(r, g, b, alpha)
+        } else {
+            // Feature: Make the unfocused selection color configurable.
+            SrgbaTuple(r, g, b, alpha * UNFOCUSED_SELECTION_ALPHA_SCALE)
         }
     }
 
-    /// A selection can be rendered in one to three rectangles.
+    /// A selection can be rendered in one to three rectangles, a rectangular selection in one.
     /// Robustness: Pass a clip rect here.
-    fn selection_rects(selection: &SelectedRange, terminal_columns: usize) -> Vec<CellRect> {
+    fn selection_rects(
+        selection: &SelectedRange,
+        terminal_columns: usize,
+        rectangular: bool,
+    ) -> Vec<CellRect> {
         assert!(terminal_columns > 0);
 
         let min = Point2D::new(0, 0);
@@ -848,6 +1531,18 @@ impl TerminalView {
         let lines_covering = end_point.y - start_point.y;
         assert!(lines_covering > 0);
 
+        if rectangular {
+            // Detail: The start column of a rectangular selection may be right of its end column.
+            let columns = selection.cols_for_row(selection.start().row, true);
+            let left = columns.start.min(terminal_columns);
+            let right = columns.end.min(terminal_columns);
+            let rect = CellRect::new(
+                (left, start_point.y).into(),
+                (right - left, lines_covering).into(),
+            );
+            return if rect.is_empty() { vec![] } else { vec![rect] };
+        }
+
         // Performance: Capacity
         let mut vecs = if lines_covering == 1 {
             vec![CellRect::new(
@@ -876,26 +1571,92 @@ impl TerminalView {
         vecs.retain(|r| !r.is_empty());
         vecs
     }
+
+    /// Lay out the rects on rows that are not rendered in logical order or with single width.
+    ///
+    /// Rects covering such rows are split up, so that every one of these rows gets its own rects.
+    /// Their columns are mapped to the visual order and scaled for double width lines.
+    fn layout_selection_rects(
+        rects: Vec<CellRect>,
+        terminal_view_geometry: &TerminalViewGeometry,
+        terminal_columns: usize,
+    ) -> Vec<CellRect> {
+        if terminal_view_geometry.line_sizes.is_empty()
+            && terminal_view_geometry.visual_orders.is_empty()
+        {
+            return rects;
+        }
+
+        let mut laid_out = Vec::with_capacity(rects.len());
+        for rect in rects {
+            let mut unchanged_start = rect.min_y();
+            for row in rect.y_range() {
+                let stable_row = row as StableRowIndex;
+                let width_scale = terminal_view_geometry.line_size(stable_row).width_scale();
+                let visual_order = terminal_view_geometry.visual_orders.get(&stable_row);
+                if width_scale == 1 && visual_order.is_none() {
+                    continue;
+                }
+                if unchanged_start < row {
+                    laid_out.push(CellRect::new(
+                        (rect.min_x(), unchanged_start).into(),
+                        (rect.width(), row - unchanged_start).into(),
+                    ));
+                }
+
+                let mut visual_columns: Vec<usize> = match visual_order {
+                    Some(visual_order) => rect
+                        .x_range()
+                        .map(|c| visual_order.visual_column(c))
+                        .collect(),
+                    None => rect.x_range().collect(),
+                };
+                visual_columns.sort_unstable();
+
+                for run in visual_columns.chunk_by(|a, b| a + 1 == *b) {
+                    let left = (run[0] * width_scale).min(terminal_columns);
+                    let right = ((run[0] + run.len()) * width_scale).min(terminal_columns);
+                    laid_out.push(CellRect::new((left, row).into(), (right - left, 1).into()));
+                }
+                unchanged_start = row + 1;
+            }
+            if unchanged_start < rect.max_y() {
+                laid_out.push(CellRect::new(
+                    (rect.min_x(), unchanged_start).into(),
+                    (rect.width(), rect.max_y() - unchanged_start).into(),
+                ));
+            }
+        }
+
+        laid_out.retain(|r| !r.is_empty());
+        laid_out
+    }
 }
 
 #[derive(Debug)]
 struct AttributeResolver<'a> {
     palette: &'a ColorPalette,
+    config: &'a Config,
     pub attributes: &'a CellAttributes,
-    foreground_color: Color,
-    // `None` indicates no background rendering.
-    background_color: Option<Color>,
+    foreground: SrgbaTuple,
+    // `None` indicates the default background, which is not rendered.
+    background: Option<SrgbaTuple>,
 }
 
 impl<'a> AttributeResolver<'a> {
-    pub fn new(palette: &'a ColorPalette, reverse_video: bool, attrs: &'a CellAttributes) -> Self {
+    pub fn new(
+        palette: &'a ColorPalette,
+        config: &'a Config,
+        reverse_video: bool,
+        attrs: &'a CellAttributes,
+    ) -> Self {
         // Precompute the ones we use multiple times.
 
         let (foreground, background) = (attrs.foreground(), attrs.background());
         let background_default = background == ColorAttribute::Default;
 
-        let foreground = Self::resolve_fg(foreground, palette, attrs);
-        let background = color::from_srgba(palette.resolve_bg(background));
+        let foreground = Self::resolve_fg(foreground, palette, config, attrs);
+        let background = palette.resolve_bg(background);
 
         let (foreground, background, background_default) = if attrs.reverse() != reverse_video {
             (background, foreground, false)
@@ -903,34 +1664,65 @@ impl<'a> AttributeResolver<'a> {
             (foreground, background, background_default)
         };
 
+        // Detail: The default background is resolved to the palette's background, which is what
+        // the cell is rendered on.
+        let foreground =
+            contrast::ensure_minimum_contrast(foreground, background, config.minimum_contrast);
+
         Self {
             palette,
+            config,
             attributes: attrs,
-            foreground_color: foreground,
-            background_color: (!background_default).then_some(background),
+            foreground,
+            background: (!background_default).then_some(background),
         }
     }
 
     pub fn underline_color(&self) -> Color {
         let color = self.attributes.underline_color();
         if color == ColorAttribute::Default {
-            return self.foreground_color;
+            return color::from_srgba(self.foreground);
         }
         // Detail: Resolving fg / bg behaves the same if the color is not the default.
-        Self::resolve_fg(color, self.palette, self.attributes)
+        color::from_srgba(Self::resolve_fg(
+            color,
+            self.palette,
+            self.config,
+            self.attributes,
+        ))
     }
 
     /// Resolve a foreground color, including bold brightening.
-    fn resolve_fg(color: ColorAttribute, palette: &ColorPalette, attrs: &CellAttributes) -> Color {
+    fn resolve_fg(
+        color: ColorAttribute,
+        palette: &ColorPalette,
+        config: &Config,
+        attrs: &CellAttributes,
+    ) -> SrgbaTuple {
         // bold brightening.
         let color = match color {
-            ColorAttribute::PaletteIndex(i) if i < 8 && attrs.intensity() == Intensity::Bold => {
+            ColorAttribute::PaletteIndex(i)
+                if i < 8
+                    && attrs.intensity() == Intensity::Bold
+                    && config.bold_brightens_ansi_colors =>
+            {
                 ColorAttribute::PaletteIndex(i + 8)
             }
             color => color,
         };
 
-        color::from_srgba(palette.resolve_fg(color))
+        palette.resolve_fg(color)
+    }
+
+    pub fn html_style(&self) -> html::CellStyle {
+        html::CellStyle {
+            foreground: html::css_color(self.foreground),
+            background: self.background.map(html::css_color),
+            bold: self.attributes.intensity() == Intensity::Bold,
+            italic: self.attributes.italic(),
+            underline: self.attributes.underline() != Underline::None,
+            strikethrough: self.attributes.strikethrough(),
+        }
     }
 
     pub fn text_weight(&self) -> TextWeight {
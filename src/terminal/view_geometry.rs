@@ -1,5 +1,5 @@
 #![allow(unused)]
-use std::{cmp::Ordering, ops::Range};
+use std::{cmp::Ordering, collections::BTreeMap, ops::Range, sync::Arc};
 
 use euclid::Point2D;
 use wezterm_term::{Cell, Screen, StableRowIndex, Terminal};
@@ -7,8 +7,8 @@ use wezterm_term::{Cell, Screen, StableRowIndex, Terminal};
 use crate::{
     range_ops::WithLength,
     terminal::{
-        CellUnit, ScreenGeometry, SelectedRange, Selection, SelectionMode, SizeCell,
-        TerminalGeometry,
+        CellUnit, LineSize, ScreenGeometry, SelectedRange, Selection, SelectionMode, SizeCell,
+        TerminalGeometry, VisualOrder,
     },
     view_geometry::PixelPoint,
 };
@@ -25,6 +25,12 @@ pub struct TerminalViewGeometry {
     ///
     /// Detail: Indices might start negative. if the view is scrolled up above the terminal's top position.
     pub stable_range: Range<StableRowIndex>,
+
+    /// The sizes of the lines in the view that are not rendered at [`LineSize::Single`].
+    pub line_sizes: BTreeMap<StableRowIndex, LineSize>,
+
+    /// The visual orders of the lines in the view that are not rendered in their logical order.
+    pub visual_orders: BTreeMap<StableRowIndex, Arc<VisualOrder>>,
 }
 
 impl TerminalViewGeometry {
@@ -49,6 +55,10 @@ impl TerminalViewGeometry {
         self.terminal.terminal_size
     }
 
+    pub fn line_size(&self, row: StableRowIndex) -> LineSize {
+        self.line_sizes.get(&row).copied().unwrap_or_default()
+    }
+
     /// Returns the currently selected user range.
     ///
     /// User range: _not_ extended by word / line boundaries, that area that was actually selected
@@ -65,18 +75,44 @@ impl TerminalViewGeometry {
     }
 
     /// Hit tests a pixel point on the view resulting in a column and a row.
+    ///
+    /// On double width lines, a column covers two cells. On lines with a visual order, the
+    /// returned column is the logical one.
     pub fn hit_test_cell(&self, view_px: PixelPoint) -> CellPos {
         let (x, mut y) = view_px.into();
 
-        let column = (x / self.terminal.cell_size_px.width as f64).floor() as isize;
-
         y -= self.stable_range_ascend_px as f64;
-        let row = (y / self.terminal.cell_size_px.height as f64).floor() as isize;
-
-        CellPos {
-            column,
-            row: row + self.stable_range.start,
+        let row = (y / self.terminal.cell_size_px.height as f64).floor() as isize
+            + self.stable_range.start;
+
+        let column_width =
+            self.terminal.cell_size_px.width as usize * self.line_size(row).width_scale();
+        let mut column = (x / column_width as f64).floor() as isize;
+        if column >= 0
+            && let Some(visual_order) = self.visual_orders.get(&row)
+        {
+            column = visual_order
+                .logical_column(column.cast_unsigned())
+                .cast_signed();
         }
+
+        CellPos { column, row }
+    }
+
+    /// The pixel offset of a view position inside the cell it hits.
+    ///
+    /// The offset is inside a single width cell, so that it can be added to the origin of the
+    /// (logical) column [`Self::hit_test_cell`] returns.
+    pub fn cell_pixel_offset(&self, view_px: PixelPoint) -> (f64, f64) {
+        let cell_pos = self.hit_test_cell(view_px);
+        let cell_size_px = self.terminal.cell_size_px;
+        let width_scale = self.line_size(cell_pos.row).width_scale() as f64;
+        let column_width = cell_size_px.width as f64 * width_scale;
+        let y = view_px.y - self.stable_range_ascend_px as f64;
+        (
+            view_px.x.rem_euclid(column_width) / width_scale,
+            y.rem_euclid(cell_size_px.height as f64),
+        )
     }
 
     pub fn get_cell<'s>(&self, cell: CellPos, screen: &'s mut Screen) -> Option<&'s Cell> {